@@ -9,7 +9,7 @@
 use chrono::{DateTime, FixedOffset, Timelike};
 use embassy_executor::Spawner;
 use embassy_net::{Config, StackResources};
-use embassy_time::{Duration, Instant, with_timeout};
+use embassy_time::{Duration, with_timeout};
 use embedded_graphics::geometry::{AnchorPoint, Size};
 use epd_waveshare::color::OctColor;
 use esp_backtrace as _;
@@ -21,13 +21,15 @@ use esp_hal::timer::timg::TimerGroup;
 use log::{error, info, warn};
 
 use todoesp_core::{
-    SetupState, TaskSnapshot, fingerprint_status, fingerprint_tasks, get_setup_tasks,
-    local_from_unix, offset_from_seconds,
+    Backoff, FetchFailure, PosixTz, RtcClock, SetupState, SyncPolicy, TaskRecord, TaskSnapshot,
+    decode_record, encode_record, fingerprint_offline, fingerprint_status, fingerprint_tasks,
+    get_setup_tasks, next_state_change, sort_tasks,
 };
 
 use todoesp32_firmware::controls::{Header, TaskList};
 use todoesp32_firmware::display::EpdDisplay;
-use todoesp32_firmware::retry::retry;
+use todoesp32_firmware::retry::RetryPolicy;
+use todoesp32_firmware::storage::TaskStore;
 use todoesp32_firmware::todoist::{ClientState, TodoistClient};
 use todoesp32_firmware::{config, net};
 
@@ -51,16 +53,44 @@ const TLS_WRITE_SIZE: usize = 4_096;
 /// Buffer for the HTTP response status line and headers (the body is streamed).
 const HTTP_RX_SIZE: usize = 8_192;
 
-/// How long the device deep-sleeps between successful refreshes.
+/// Longest deep sleep between successful refreshes; the device wakes sooner
+/// when a task starts or ends, the date rolls over, or a fetch is due (see
+/// `config::TODOIST_POLL_MINUTES`).
 const REFRESH_INTERVAL: Duration = Duration::from_secs(300);
-/// Shorter deep-sleep used to retry after a connectivity or fetch failure.
+/// Added to warnings about timezones that don't resolve.
+#[cfg(feature = "tzdb")]
+const TZ_HINT: &str = "";
+#[cfg(not(feature = "tzdb"))]
+const TZ_HINT: &str = " (IANA zone names need the `tzdb` feature)";
+
+/// Shorter deep-sleep used to retry after a connectivity or fetch failure. It
+/// doubles with each consecutive failed wake, up to [`MAX_RETRY_INTERVAL`].
 const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Longest deep sleep between retries during a long outage.
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(30 * 60);
 /// How long to wait for the WiFi link (association + DHCP) before giving up.
 const WIFI_TIMEOUT: Duration = Duration::from_secs(30);
 /// How long to wait for a single NTP time sync attempt before giving up.
 const SNTP_TIMEOUT: Duration = Duration::from_secs(15);
-/// How many additional NTP sync attempts to make after the first failure.
-const SNTP_RETRIES: usize = 3;
+/// Retries of a single NTP server before moving on to the next.
+const SNTP_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 4,
+    backoff: Backoff {
+        base_micros: 500_000,
+        max_micros: 4_000_000,
+    },
+    budget: Duration::from_secs(45),
+};
+/// Retries of a Todoist request. Only network trouble and server errors are
+/// retried; anything else (e.g. a rejected token) would fail the same way.
+const TODOIST_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    backoff: Backoff {
+        base_micros: 2_000_000,
+        max_micros: 8_000_000,
+    },
+    budget: Duration::from_secs(60),
+};
 
 /// Marks [`DISPLAY_FINGERPRINT`] as valid. RTC memory holds undefined contents
 /// after a cold boot, so the stored fingerprint is only trusted when the
@@ -77,17 +107,111 @@ static mut DISPLAY_FINGERPRINT: u64 = 0;
 #[esp_hal::ram(unstable(rtc_fast, persistent))]
 static mut DISPLAY_FINGERPRINT_VALID: u32 = 0;
 
+/// Marks [`FAILURE_COUNT`] as valid, in the same way as [`FINGERPRINT_MAGIC`].
+const FAILURE_MAGIC: u32 = 0x7d0e_fa11;
+
+/// How many wakes in a row have failed to refresh, kept in RTC fast memory so
+/// the retry interval can back off during a long outage.
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut FAILURE_COUNT: u32 = 0;
+/// Validity marker for [`FAILURE_COUNT`]; see [`FAILURE_MAGIC`].
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut FAILURE_COUNT_VALID: u32 = 0;
+
+/// Marks the persisted clock ([`CLOCK_UNIX_MICROS`] and friends) as valid, in
+/// the same way as [`FINGERPRINT_MAGIC`].
+const CLOCK_MAGIC: u32 = 0x7d0e_c10c;
+
+/// Unix time (µs) at the last clock sync, kept in RTC fast memory so wakes can
+/// derive the time from the RTC instead of syncing again; see [`RtcClock`].
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut CLOCK_UNIX_MICROS: i64 = 0;
+/// RTC reading (µs) at the last clock sync.
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut CLOCK_RTC_MICROS: u64 = 0;
+/// Measured RTC rate error in parts per billion, or `i32::MIN` if unknown.
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut CLOCK_DRIFT_PPB: i32 = 0;
+/// Where the persisted clock was last set from, as a [`ClockSource`].
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut CLOCK_SOURCE: u32 = 0;
+/// Validity marker for the persisted clock; see [`CLOCK_MAGIC`].
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut CLOCK_VALID: u32 = 0;
+
+/// Marks [`PROFILE_UNIX`] as valid, in the same way as [`FINGERPRINT_MAGIC`].
+const PROFILE_MAGIC: u32 = 0x7d0e_7a11;
+
+/// How often the Todoist account's timezone is fetched when `config::TZ` is
+/// empty. In between, the one stored with the task list is used.
+const PROFILE_INTERVAL_SECS: i64 = 24 * 3_600;
+
+/// Unix time (s) at which the Todoist account's timezone was last fetched, kept
+/// in RTC fast memory; see [`PROFILE_INTERVAL_SECS`].
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut PROFILE_UNIX: i64 = 0;
+/// Validity marker for [`PROFILE_UNIX`]; see [`PROFILE_MAGIC`].
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut PROFILE_UNIX_VALID: u32 = 0;
+
+/// Room for the task list cached in RTC memory. RTC fast memory is only 8 KiB,
+/// so a very long list is cut short (see [`encode_record`]); the display can't
+/// show more than a screenful anyway, and the record still counts the rest.
+const TASK_CACHE_SIZE: usize = 4_096;
+
+/// The tasks from the last fetch, as a [`TaskRecord`], so wakes between
+/// fetches can recompute their labels without WiFi. The record carries its own
+/// magic number and checksum, so unlike the values above it needs no separate
+/// validity marker. A copy is kept in flash by [`TaskStore`].
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut TASK_CACHE: [u8; TASK_CACHE_SIZE] = [0; TASK_CACHE_SIZE];
+
+/// The outcome of a successful refresh, from the network or the task cache.
+struct Refresh {
+    /// The local time the snapshots were computed for.
+    now: DateTime<FixedOffset>,
+    /// When the tasks were fetched from Todoist.
+    fetched_at: DateTime<FixedOffset>,
+    snapshots: alloc::vec::Vec<TaskSnapshot>,
+    /// How many tasks were fetched; `snapshots` may cover only the first.
+    total: usize,
+    /// When the snapshots next change by themselves; see [`next_state_change`].
+    next_change: DateTime<FixedOffset>,
+}
+
+/// Where the persisted clock was last set from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ClockSource {
+    Ntp,
+    /// The `Date` header of a Todoist response, when no NTP server answered.
+    /// It is only good to a second or so: enough to re-render cached tasks,
+    /// but the next fetch still tries NTP first, and it is never used to
+    /// measure the RTC's rate.
+    HttpDate,
+}
+
 /// The kind of problem that prevented a normal refresh. Each renders a distinct
 /// status screen and is fingerprinted, so a persistent failure is only drawn
 /// once rather than on every retry.
 #[derive(Clone, Copy, Debug)]
 enum Failure {
     /// Could not associate with WiFi / obtain an IP address.
-    Wifi = 0,
+    Wifi,
     /// Connected, but could not synchronise the clock over NTP.
-    Time = 1,
-    /// Connected with a synced clock, but could not fetch tasks from Todoist.
-    Fetch = 2,
+    Time,
+    /// Connected, but could not fetch tasks from Todoist.
+    Fetch(FetchFailure),
+}
+
+impl Failure {
+    /// The code identifying this failure's status screen.
+    fn code(self) -> u8 {
+        match self {
+            Self::Wifi => 0,
+            Self::Time => 1,
+            Self::Fetch(failure) => failure.status_code(),
+        }
+    }
 }
 
 #[esp_rtos::main]
@@ -113,6 +237,8 @@ async fn main(spawner: Spawner) -> ! {
     let shown = load_fingerprint();
 
     let mut rng = Rng::new();
+    let rtc = Rtc::new(peripherals.LPWR);
+    let mut store = TaskStore::new(peripherals.FLASH);
 
     let mut display = match EpdDisplay::new(
         peripherals.SPI2,
@@ -131,7 +257,19 @@ async fn main(spawner: Spawner) -> ! {
     };
     info!("Display configured");
 
-    let offset = offset_from_seconds(config::UTC_OFFSET_SECONDS);
+    // An empty `TZ` defers to the Todoist account's timezone, fetched during
+    // the refresh.
+    let tz = (!config::TZ.is_empty()).then(|| {
+        PosixTz::parse(config::TZ)
+            .or_else(|| PosixTz::from_name(config::TZ))
+            .unwrap_or_else(|| {
+                error!(
+                    "Unknown timezone {:?}{TZ_HINT}; falling back to UTC",
+                    config::TZ
+                );
+                PosixTz::utc()
+            })
+    });
     let mut header = Header::new();
     let mut tasks = TaskList::new(display.bounding_box().resized(
         Size::new(display.width() as u32, display.height() as u32 - 30),
@@ -140,41 +278,115 @@ async fn main(spawner: Spawner) -> ! {
 
     // Run a single refresh cycle, render only if the result changed, then sleep.
     // Deep sleep resets the chip, so the next wake starts this function over.
-    let (fingerprint, sleep_for) = match run_refresh(spawner, &mut rng, peripherals.WIFI, offset)
-        .await
-    {
-        Ok((now, snapshots)) => {
-            let date = now.date_naive();
-            let fingerprint = fingerprint_tasks(date, &snapshots);
+    // Between fetches the cached tasks are re-rendered without touching WiFi.
+    let refresh = match refresh_from_cache(&rtc, tz) {
+        Some(refresh) => Ok(refresh),
+        None => run_refresh(spawner, &mut rng, &rtc, peripherals.WIFI, tz, &mut store).await,
+    };
+    let (fingerprint, sleep_for) = match refresh {
+        Ok(refresh) => {
+            store_failure_count(0);
+            let fingerprint =
+                fingerprint_tasks(refresh.now.date_naive(), &refresh.snapshots, refresh.total);
+            // Wake early if a task starts or ends, the date rolls over, or
+            // the next fetch is due.
+            let next_fetch = refresh.fetched_at + poll_interval();
+            let until = refresh.next_change.min(next_fetch) - refresh.now;
             if shown == Some(fingerprint) {
                 info!("Tasks unchanged (fingerprint {fingerprint:#018x}); leaving the panel as-is");
             } else {
                 info!(
                     "Task content changed (was {shown:?}, now {fingerprint:#018x}); refreshing the panel"
                 );
-                header.set_date(date);
-                header.set_last_update(
-                    alloc::format!("Updated {:02}:{:02}", now.hour(), now.minute()),
+                let fetched_at = refresh.fetched_at;
+                let message = alloc::format!(
+                    "Updated {:02}:{:02}",
+                    fetched_at.hour(),
+                    fetched_at.minute()
+                );
+                render_tasks(
+                    &mut display,
+                    &mut header,
+                    &mut tasks,
+                    refresh,
+                    message,
                     OctColor::Green,
                 );
-                tasks.set_tasks(snapshots);
-                if let Err(e) = display
-                    .render_controls_if_dirty(OctColor::White, &mut [&mut header, &mut tasks])
-                {
-                    error!("Failed to render task list: {e:?}");
-                }
             }
-            (fingerprint, REFRESH_INTERVAL)
+            (
+                fingerprint,
+                REFRESH_INTERVAL.min(Duration::from_secs(until.num_seconds().max(1) as u64)),
+            )
         }
         Err(failure) => {
-            let fingerprint = fingerprint_status(failure as u8);
-            if shown == Some(fingerprint) {
-                warn!("Refresh still failing ({failure:?}); status screen already shown");
-            } else {
-                warn!("Refresh failed ({failure:?}); showing the status screen");
-                render_status(&mut display, &mut header, &mut tasks, failure, offset);
+            // Keep showing the last good task list through a WiFi or Todoist
+            // outage; the setup checklist is only useful when nothing was ever
+            // fetched, or the clock can't be trusted.
+            let offline = match failure {
+                Failure::Wifi => refresh_offline(&rtc, tz, &mut store),
+                Failure::Fetch(failure) if failure.is_transient() => {
+                    refresh_offline(&rtc, tz, &mut store)
+                }
+                Failure::Time | Failure::Fetch(_) => None,
+            };
+            let fingerprint = match offline {
+                Some(refresh) => {
+                    let since = refresh.fetched_at;
+                    let fingerprint = fingerprint_offline(
+                        refresh.now.date_naive(),
+                        since.time(),
+                        &refresh.snapshots,
+                        refresh.total,
+                    );
+                    if shown == Some(fingerprint) {
+                        warn!("Refresh still failing ({failure:?}); cached tasks already shown");
+                    } else {
+                        warn!("Refresh failed ({failure:?}); showing the cached tasks");
+                        let message = alloc::format!(
+                            "Offline since {:02}:{:02}",
+                            since.hour(),
+                            since.minute()
+                        );
+                        render_tasks(
+                            &mut display,
+                            &mut header,
+                            &mut tasks,
+                            refresh,
+                            message,
+                            OctColor::Red,
+                        );
+                    }
+                    fingerprint
+                }
+                None => {
+                    let fingerprint = fingerprint_status(failure.code());
+                    if shown == Some(fingerprint) {
+                        warn!("Refresh still failing ({failure:?}); status screen already shown");
+                    } else {
+                        warn!("Refresh failed ({failure:?}); showing the status screen");
+                        let tz = tz.unwrap_or_else(PosixTz::utc);
+                        render_status(&mut display, &mut header, &mut tasks, failure, &tz);
+                    }
+                    fingerprint
+                }
+            };
+            // Back off while the failures continue, and honour a rate limit's
+            // `Retry-After` (capped at `MAX_RETRY_AFTER_SECS`) rather than
+            // retrying sooner.
+            let failures = load_failure_count().saturating_add(1);
+            store_failure_count(failures);
+            let backoff = Backoff {
+                base_micros: RETRY_INTERVAL.as_micros(),
+                max_micros: MAX_RETRY_INTERVAL.as_micros(),
+            };
+            let mut sleep_for = Duration::from_micros(backoff.ceiling_micros(failures));
+            if let Failure::Fetch(failure) = failure
+                && let Some(secs) = failure.retry_after_secs()
+            {
+                sleep_for = sleep_for.max(Duration::from_secs(secs));
             }
-            (fingerprint, RETRY_INTERVAL)
+            info!("{failures} failed refreshes in a row");
+            (fingerprint, sleep_for)
         }
     };
 
@@ -184,20 +396,90 @@ async fn main(spawner: Spawner) -> ! {
     // keeps its image with no power, so the display stays visible until the
     // next refresh actually changes something.
     display.sleep();
-    enter_deep_sleep(sleep_for, peripherals.LPWR);
+    enter_deep_sleep(sleep_for, rtc);
+}
+
+/// Recompute the snapshots of the cached tasks, if a fetch isn't due yet.
+///
+/// Only the time-dependent labels change between fetches, so this needs no
+/// network at all: the time comes from the RTC and the tasks from RTC memory.
+/// Returns `None` when the cache or clock can't be trusted, or the tasks are
+/// due to be fetched again.
+fn refresh_from_cache(rtc: &Rtc<'_>, tz: Option<PosixTz>) -> Option<Refresh> {
+    let rtc_now = rtc.current_time_us();
+    let (clock, _) =
+        load_clock().filter(|(clock, _)| !clock.needs_sync(rtc_now, &sync_policy()))?;
+    let now_unix = clock.now(rtc_now)?.div_euclid(1_000_000);
+
+    let refresh = cached_refresh(load_task_cache()?, now_unix, tz)?;
+    if refresh.now < refresh.fetched_at || refresh.now >= refresh.fetched_at + poll_interval() {
+        return None;
+    }
+    info!(
+        "Re-rendering {} cached tasks without WiFi",
+        refresh.snapshots.len()
+    );
+    Some(refresh)
+}
+
+/// Recompute the snapshots of the last good task list after a failed refresh.
+///
+/// The list comes from RTC memory or, after a power cycle, from flash. The
+/// time comes from the RTC even if it is due a resync, as a slightly drifted
+/// clock beats no task list at all. Returns `None` if there is no stored task
+/// list, or the clock was never set.
+fn refresh_offline(
+    rtc: &Rtc<'_>,
+    tz: Option<PosixTz>,
+    store: &mut TaskStore<'_>,
+) -> Option<Refresh> {
+    let (clock, _) = load_clock()?;
+    let now_unix = clock.now(rtc.current_time_us())?.div_euclid(1_000_000);
+    let record = load_task_cache().or_else(|| {
+        store
+            .load()
+            .inspect_err(|e| warn!("No task list in flash: {e:?}"))
+            .ok()
+    })?;
+    cached_refresh(record, now_unix, tz)
+}
+
+/// Recompute the snapshots of a stored task list at `now_unix`, in the
+/// configured timezone or else the one stored with the tasks. Without either,
+/// the tasks were shown in UTC, and still are.
+fn cached_refresh(record: TaskRecord, now_unix: i64, tz: Option<PosixTz>) -> Option<Refresh> {
+    let tz = tz.or(record.timezone).unwrap_or_else(PosixTz::utc);
+    let now = tz.local_from_unix(now_unix)?;
+    let fetched_at = tz.local_from_unix(record.fetched_unix)?;
+    let next_change = next_state_change(&record.tasks, now);
+    let snapshots = record
+        .tasks
+        .into_iter()
+        .map(|t| t.into_snapshot(now))
+        .collect();
+    Some(Refresh {
+        now,
+        fetched_at,
+        snapshots,
+        total: record.total,
+        next_change,
+    })
 }
 
 /// Connect to WiFi, synchronise the clock and fetch the current tasks.
 ///
-/// On success returns the snapshots to display and the time they were fetched;
+/// On success returns the snapshots to display, the time they were fetched and
+/// when they will next change by themselves (see [`next_state_change`]);
 /// otherwise returns the [`Failure`] that stopped us. Everything it allocates is
 /// leaked for the cycle and reclaimed by the deep-sleep reset.
 async fn run_refresh(
     spawner: Spawner,
     rng: &mut Rng,
+    rtc: &Rtc<'_>,
     wifi: esp_hal::peripherals::WIFI<'static>,
-    offset: FixedOffset,
-) -> Result<(DateTime<FixedOffset>, alloc::vec::Vec<TaskSnapshot>), Failure> {
+    tz: Option<PosixTz>,
+    store: &mut TaskStore<'_>,
+) -> Result<Refresh, Failure> {
     let (controller, interfaces) = esp_radio::wifi::new(wifi, Default::default()).map_err(|e| {
         error!("Failed to initialise WiFi: {e:?}");
         Failure::Wifi
@@ -230,64 +512,255 @@ async fn run_refresh(
         info!("Got IP address: {}", cfg.address);
     }
 
-    let boot = Instant::now();
-    let base_unix = retry(
-        || async {
-            match with_timeout(
-                SNTP_TIMEOUT,
-                todoesp32_firmware::sntp::sync_unix_time(stack, config::NTP_SERVER),
+    // The RTC kept counting through deep sleep, so the time persisted at the
+    // last NTP sync is good enough until it is old or may have drifted too far.
+    let stored = load_clock();
+    let rtc_now = rtc.current_time_us();
+    let mut clock = stored
+        .filter(|(stored, source)| {
+            *source == ClockSource::Ntp && !stored.needs_sync(rtc_now, &sync_policy())
+        })
+        .and_then(|(stored, _)| stored.now(rtc_now))
+        .map(|unix| unix - todoesp32_firmware::sntp::client_micros());
+    if clock.is_some() {
+        info!("System time restored from the RTC; skipping NTP");
+    }
+
+    // Otherwise try each NTP server in turn. If UDP is blocked altogether,
+    // the `Date` header of the Todoist responses stands in once the tasks
+    // are fetched.
+    for server in config::NTP_SERVERS {
+        if clock.is_some() {
+            break;
+        }
+        let synced = SNTP_RETRY
+            .run(
+                async || match with_timeout(
+                    SNTP_TIMEOUT,
+                    todoesp32_firmware::sntp::sync(stack, server),
+                )
+                .await
+                {
+                    Ok(result) => result,
+                    Err(_) => Err(todoesp32_firmware::sntp::SntpError::Timeout),
+                },
+                todoesp32_firmware::sntp::SntpError::is_retryable,
             )
-            .await
-            {
-                Ok(result) => result,
-                Err(_) => Err(todoesp32_firmware::sntp::SntpError::Timeout),
+            .await;
+        match synced {
+            Ok(sample) => {
+                info!(
+                    "System time synchronised via NTP from {server} (stratum {}, round trip {} ms)",
+                    sample.stratum,
+                    sample.delay_micros / 1_000
+                );
+                let unix = sample.unix_micros(todoesp32_firmware::sntp::client_micros());
+                let rtc_now = rtc.current_time_us();
+                let synced = match stored {
+                    Some((stored, ClockSource::Ntp)) => stored.resync(unix, rtc_now),
+                    // Too coarse to measure the RTC's rate against.
+                    Some((stored, ClockSource::HttpDate)) => {
+                        RtcClock::from_parts(unix, rtc_now, stored.drift_ppb())
+                    }
+                    None => RtcClock::new(unix, rtc_now),
+                };
+                store_clock(synced, ClockSource::Ntp);
+                clock = Some(sample.offset_micros);
             }
-        },
-        SNTP_RETRIES,
-    )
-    .await
-    .map_err(|e| {
-        warn!("Failed to synchronise time over NTP: {e:?}");
-        Failure::Time
-    })?;
-    info!("System time synchronised via NTP");
+            Err(e) => warn!("Failed to synchronise time with {server}: {e:?}"),
+        }
+    }
 
     // Heap-leaked for this cycle; the deep-sleep reset reclaims everything.
     let tcp_state: &'static ClientState =
         alloc::boxed::Box::leak(alloc::boxed::Box::new(ClientState::new()));
-    let todoist = TodoistClient::new(config::TODOIST_API_KEY, config::TODOIST_FILTER, tcp_state);
+    let todoist = TodoistClient::new(config::TODOIST_API_KEY, config::TODOIST_FILTER, tcp_state)
+        .with_max_pages(config::TODOIST_MAX_PAGES);
     let tls_read: &'static mut [u8] = alloc::vec![0u8; TLS_READ_SIZE].leak();
     let tls_write: &'static mut [u8] = alloc::vec![0u8; TLS_WRITE_SIZE].leak();
     let rx_buf: &'static mut [u8] = alloc::vec![0u8; HTTP_RX_SIZE].leak();
 
-    let now_unix = base_unix + Instant::now().duration_since(boot).as_secs() as i64;
-    let now = local_from_unix(now_unix, offset).ok_or(Failure::Time)?;
-
-    let mut fetched = None;
-    for attempt in 0..3 {
-        let request_seed = seed(rng);
-        match todoist
-            .get_tasks(
-                stack,
-                request_seed,
-                &mut tls_read[..],
-                &mut tls_write[..],
-                &mut rx_buf[..],
-            )
-            .await
-        {
-            Ok(tasks) => {
-                fetched = Some(tasks);
-                break;
-            }
-            Err(e) => warn!("Failed to fetch tasks (attempt {}): {e:?}", attempt + 1),
+    // The account's timezone rarely changes, so the one stored with the last
+    // task list is reused between profile fetches, and through a failed one.
+    let (tz, account_tz) = match tz {
+        Some(tz) => (tz, None),
+        None => {
+            let stored_tz = load_task_cache()
+                .or_else(|| store.load().ok())
+                .and_then(|record| record.timezone);
+            let now_unix = clock
+                .map(|offset| todoesp32_firmware::sntp::client_micros() + offset)
+                .or_else(|| stored.and_then(|(stored, _)| stored.now(rtc.current_time_us())))
+                .map(|micros| micros.div_euclid(1_000_000));
+            let fetched = if stored_tz.is_none() || profile_due(now_unix) {
+                let fetched =
+                    fetch_account_tz(&todoist, stack, rng, tls_read, tls_write, rx_buf).await;
+                if fetched.is_some()
+                    && let Some(now_unix) = now_unix
+                {
+                    store_profile_time(now_unix);
+                }
+                fetched
+            } else {
+                info!("Reusing the stored Todoist timezone");
+                None
+            };
+            let account_tz = fetched.or(stored_tz);
+            let tz = account_tz.unwrap_or_else(|| {
+                warn!("No Todoist timezone known; using UTC");
+                PosixTz::utc()
+            });
+            (tz, account_tz)
         }
-    }
-    let fetched = fetched.ok_or(Failure::Fetch)?;
+    };
+
+    let mut fetched = TODOIST_RETRY
+        .run(
+            async || {
+                todoist
+                    .get_tasks(
+                        stack,
+                        seed(rng),
+                        &mut tls_read[..],
+                        &mut tls_write[..],
+                        &mut rx_buf[..],
+                    )
+                    .await
+            },
+            |e| e.classify().is_retryable(),
+        )
+        .await
+        .map_err(|e| Failure::Fetch(e.classify()))?;
     info!("Fetched {} tasks from Todoist", fetched.len());
 
-    let snapshots = fetched.into_iter().map(|t| t.into_snapshot(now)).collect();
-    Ok((now, snapshots))
+    let clock = match clock {
+        Some(clock) => clock,
+        None => {
+            let clock = todoist.server_clock().ok_or_else(|| {
+                warn!("No NTP server answered and Todoist sent no Date header");
+                Failure::Time
+            })?;
+            info!("System time taken from the Todoist Date header");
+            // Keep it for the wakes between fetches, which would otherwise
+            // have no clock at all on a network that blocks NTP.
+            let unix = todoesp32_firmware::sntp::client_micros() + clock;
+            let drift_ppb = stored.and_then(|(stored, _)| stored.drift_ppb());
+            store_clock(
+                RtcClock::from_parts(unix, rtc.current_time_us(), drift_ppb),
+                ClockSource::HttpDate,
+            );
+            clock
+        }
+    };
+    let now_unix = (todoesp32_firmware::sntp::client_micros() + clock).div_euclid(1_000_000);
+    let now = tz.local_from_unix(now_unix).ok_or(Failure::Time)?;
+    sort_tasks(&mut fetched, now.offset());
+
+    let record = TaskRecord {
+        fetched_unix: now_unix,
+        timezone: account_tz,
+        total: fetched.len(),
+        tasks: fetched,
+    };
+    store_task_cache(&record);
+    if let Err(e) = store.store(&record) {
+        warn!("Failed to save the task list to flash: {e:?}");
+    }
+    // Show just what the cache kept, so that the wakes re-rendering it draw
+    // the same screen, with the same fingerprint.
+    let record = load_task_cache().unwrap_or(record);
+
+    let next_change = next_state_change(&record.tasks, now);
+    let snapshots = record
+        .tasks
+        .into_iter()
+        .map(|t| t.into_snapshot(now))
+        .collect();
+    Ok(Refresh {
+        now,
+        fetched_at: now,
+        snapshots,
+        total: record.total,
+        next_change,
+    })
+}
+
+/// Fetch the Todoist account's timezone from the user profile, or `None` if
+/// the request fails or the zone is unusable.
+async fn fetch_account_tz(
+    todoist: &TodoistClient,
+    stack: embassy_net::Stack<'static>,
+    rng: &mut Rng,
+    tls_read: &mut [u8],
+    tls_write: &mut [u8],
+    rx_buf: &mut [u8],
+) -> Option<PosixTz> {
+    let profile = TODOIST_RETRY
+        .run(
+            async || {
+                todoist
+                    .get_user(
+                        stack,
+                        seed(rng),
+                        &mut tls_read[..],
+                        &mut tls_write[..],
+                        &mut rx_buf[..],
+                    )
+                    .await
+            },
+            |e| e.classify().is_retryable(),
+        )
+        .await
+        .inspect_err(|e| warn!("Failed to fetch the Todoist user profile: {e:?}"))
+        .ok()?;
+    let tz = profile.tz();
+    if tz.is_none() {
+        warn!(
+            "Todoist timezone {:?} is not usable{TZ_HINT}",
+            profile.timezone()
+        );
+    }
+    tz
+}
+
+/// Whether the Todoist account's timezone should be fetched again at
+/// `now_unix`: every [`PROFILE_INTERVAL_SECS`], and whenever the time of the
+/// last fetch or the current time is unknown.
+fn profile_due(now_unix: Option<i64>) -> bool {
+    match (load_profile_time(), now_unix) {
+        (Some(last), Some(now)) => !(0..PROFILE_INTERVAL_SECS).contains(&(now - last)),
+        _ => true,
+    }
+}
+
+/// When the RTC-kept clock must be resynchronised over NTP.
+fn sync_policy() -> SyncPolicy {
+    SyncPolicy {
+        max_interval_micros: u64::from(config::NTP_RESYNC_HOURS) * 3_600_000_000,
+        max_error_micros: u64::from(config::MAX_CLOCK_DRIFT_MS) * 1_000,
+    }
+}
+
+/// How long cached tasks are shown before they are fetched again.
+fn poll_interval() -> chrono::TimeDelta {
+    chrono::TimeDelta::minutes(config::TODOIST_POLL_MINUTES.into())
+}
+
+/// Show a task list, headed by a status message such as the last update time.
+fn render_tasks(
+    display: &mut EpdDisplay,
+    header: &mut Header,
+    tasks: &mut TaskList,
+    refresh: Refresh,
+    message: alloc::string::String,
+    colour: OctColor,
+) {
+    header.set_date(refresh.now.date_naive());
+    header.set_last_update(message, colour);
+    tasks.set_tasks_of(refresh.snapshots, refresh.total);
+    if let Err(e) = display.render_controls_if_dirty(OctColor::White, &mut [header, tasks]) {
+        error!("Failed to render task list: {e:?}");
+    }
 }
 
 /// Render the status checklist describing why a refresh failed.
@@ -296,21 +769,28 @@ fn render_status(
     header: &mut Header,
     tasks: &mut TaskList,
     failure: Failure,
-    offset: FixedOffset,
+    tz: &PosixTz,
 ) {
-    let now = local_from_unix(0, offset).unwrap_or_default();
+    let now = tz.local_from_unix(0).unwrap_or_default();
     let state = SetupState {
         wifi_configured: !config::WIFI_SSID.is_empty(),
-        todoist_configured: !config::TODOIST_API_KEY.is_empty(),
+        // A rejected token needs configuring again just like a missing one.
+        todoist_configured: !config::TODOIST_API_KEY.is_empty()
+            && !matches!(failure, Failure::Fetch(FetchFailure::Unauthorized)),
         wifi_connected: !matches!(failure, Failure::Wifi),
-        time_synced: matches!(failure, Failure::Fetch),
+        time_synced: matches!(failure, Failure::Fetch(_)),
     };
     tasks.set_tasks(get_setup_tasks(now, state));
 
     let message = match failure {
         Failure::Wifi => "WiFi unavailable",
         Failure::Time => "Clock sync failed",
-        Failure::Fetch => "Todoist unreachable",
+        Failure::Fetch(FetchFailure::Network) => "Todoist unreachable",
+        Failure::Fetch(FetchFailure::Unauthorized) => "API token rejected",
+        Failure::Fetch(FetchFailure::RateLimited(_)) => "Todoist rate-limited",
+        Failure::Fetch(FetchFailure::Server(_)) => "Todoist server error",
+        Failure::Fetch(FetchFailure::Rejected(_)) => "Todoist refused request",
+        Failure::Fetch(FetchFailure::Parse) => "Bad Todoist response",
     };
     header.set_last_update(message.into(), OctColor::Red);
 
@@ -341,18 +821,117 @@ fn store_fingerprint(value: u64) {
     }
 }
 
+/// Read the number of consecutive failed refreshes persisted across deep
+/// sleep, or 0 if RTC memory does not hold a valid count.
+fn load_failure_count() -> u32 {
+    // SAFETY: single-threaded access to RTC-persistent memory.
+    unsafe {
+        if (&raw const FAILURE_COUNT_VALID).read() == FAILURE_MAGIC {
+            (&raw const FAILURE_COUNT).read()
+        } else {
+            0
+        }
+    }
+}
+
+/// Persist the number of consecutive failed refreshes across deep sleep.
+fn store_failure_count(value: u32) {
+    // SAFETY: single-threaded access to RTC-persistent memory.
+    unsafe {
+        (&raw mut FAILURE_COUNT).write(value);
+        (&raw mut FAILURE_COUNT_VALID).write(FAILURE_MAGIC);
+    }
+}
+
+/// Read the clock persisted at the last sync and where it came from, or `None`
+/// if RTC memory does not hold one.
+fn load_clock() -> Option<(RtcClock, ClockSource)> {
+    // SAFETY: single-threaded access to RTC-persistent memory.
+    unsafe {
+        if (&raw const CLOCK_VALID).read() != CLOCK_MAGIC {
+            return None;
+        }
+        let drift = (&raw const CLOCK_DRIFT_PPB).read();
+        let clock = RtcClock::from_parts(
+            (&raw const CLOCK_UNIX_MICROS).read(),
+            (&raw const CLOCK_RTC_MICROS).read(),
+            (drift != i32::MIN).then_some(drift),
+        );
+        let source = match (&raw const CLOCK_SOURCE).read() {
+            0 => ClockSource::Ntp,
+            _ => ClockSource::HttpDate,
+        };
+        Some((clock, source))
+    }
+}
+
+/// Persist the clock, where it came from and its validity marker across deep
+/// sleep.
+fn store_clock(clock: RtcClock, source: ClockSource) {
+    // SAFETY: single-threaded access to RTC-persistent memory.
+    unsafe {
+        (&raw mut CLOCK_UNIX_MICROS).write(clock.unix_micros());
+        (&raw mut CLOCK_RTC_MICROS).write(clock.rtc_micros());
+        (&raw mut CLOCK_DRIFT_PPB).write(clock.drift_ppb().unwrap_or(i32::MIN));
+        (&raw mut CLOCK_SOURCE).write(match source {
+            ClockSource::Ntp => 0,
+            ClockSource::HttpDate => 1,
+        });
+        (&raw mut CLOCK_VALID).write(CLOCK_MAGIC);
+    }
+}
+
+/// Read when the Todoist account's timezone was last fetched, or `None` if RTC
+/// memory does not hold a valid time.
+fn load_profile_time() -> Option<i64> {
+    // SAFETY: single-threaded access to RTC-persistent memory.
+    unsafe {
+        if (&raw const PROFILE_UNIX_VALID).read() == PROFILE_MAGIC {
+            Some((&raw const PROFILE_UNIX).read())
+        } else {
+            None
+        }
+    }
+}
+
+/// Persist when the Todoist account's timezone was last fetched across deep
+/// sleep.
+fn store_profile_time(unix: i64) {
+    // SAFETY: single-threaded access to RTC-persistent memory.
+    unsafe {
+        (&raw mut PROFILE_UNIX).write(unix);
+        (&raw mut PROFILE_UNIX_VALID).write(PROFILE_MAGIC);
+    }
+}
+
+/// Read the task list persisted at the last fetch, or `None` if RTC memory
+/// does not hold a valid one.
+fn load_task_cache() -> Option<TaskRecord> {
+    // SAFETY: single-threaded access to RTC-persistent memory.
+    let bytes = unsafe { &*(&raw const TASK_CACHE) };
+    decode_record(bytes)
+        .inspect_err(|e| info!("No cached task list: {e}"))
+        .ok()
+}
+
+/// Persist the fetched task list (as many tasks as fit) across deep sleep.
+fn store_task_cache(record: &TaskRecord) {
+    // SAFETY: single-threaded access to RTC-persistent memory.
+    let bytes = unsafe { &mut *(&raw mut TASK_CACHE) };
+    encode_record(record, bytes);
+}
+
 /// Enter timer-wake deep sleep. The chip resets on wake and `main` runs again.
 ///
 /// Unlike [`Rtc::sleep_deep`], this keeps the RTC fast-memory domain powered so
-/// the persistent [`DISPLAY_FINGERPRINT`] actually survives the sleep — the
-/// default deep-sleep config powers that memory down, which would erase the
-/// fingerprint and force a full refresh on every wake.
-fn enter_deep_sleep(duration: Duration, lpwr: esp_hal::peripherals::LPWR<'static>) -> ! {
+/// the persistent [`DISPLAY_FINGERPRINT`], clock and task cache actually survive
+/// the sleep — the default deep-sleep config powers that memory down, which would
+/// erase the fingerprint and force a full refresh (and NTP sync) on every wake.
+fn enter_deep_sleep(duration: Duration, mut rtc: Rtc<'static>) -> ! {
     info!("Entering deep sleep for {} s", duration.as_secs());
     let mut config = RtcSleepConfig::deep();
     config.set_rtc_fastmem_pd_en(false);
 
-    let mut rtc = Rtc::new(lpwr);
     let wake = TimerWakeupSource::new(core::time::Duration::from_secs(duration.as_secs()));
     rtc.sleep(&config, &[&wake]);
     unreachable!("deep sleep resets the chip and never returns");
@@ -5,11 +5,34 @@ pub const WIFI_PASSWORD: &str = "";
 
 pub const TODOIST_API_KEY: &str = "";
 pub const TODOIST_FILTER: &str = "today & !subtask & (!shared | assigned to:me)";
+// Maximum number of 200-task result pages fetched per refresh. Tasks beyond
+// this limit are dropped (with a warning) to bound memory use on busy accounts.
+pub const TODOIST_MAX_PAGES: usize = 3;
+// Minutes between fetches from Todoist. In between, the device wakes without
+// WiFi to update time-dependent labels (e.g. "in 5 min" becoming "now") from
+// the tasks it fetched last, which saves a lot of battery.
+pub const TODOIST_POLL_MINUTES: u32 = 30;
 
-// Fixed UTC offset in seconds applied to all timestamps. no_std builds cannot
-// evaluate POSIX TZ/DST rules, so this must be updated manually for DST changes.
-// Example: Ireland is UTC+1 (3600) in summer (IST) and UTC+0 (0) in winter (GMT).
-pub const UTC_OFFSET_SECONDS: i32 = 0;
+// Local timezone, including daylight saving transitions: either an IANA zone
+// name from the embedded table (e.g. "Europe/Dublin") or a POSIX TZ rule. The
+// rule for any IANA zone is the last line of its file in /usr/share/zoneinfo,
+// e.g. `tail -n1 /usr/share/zoneinfo/Europe/Dublin`. Examples:
+//   Ireland:  "GMT0IST,M3.5.0/1,M10.5.0"
+//   New York: "EST5EDT,M3.2.0,M11.1.0"
+//   Sydney:   "AEST-10AEDT,M10.1.0,M4.1.0/3"
+// Leave empty to use the timezone set in your Todoist account, which is looked
+// up once a day and kept with the cached task list in between. Zone names need
+// the `tzdb` feature (on by default); build with `--no-default-features` to
+// save flash if you use a POSIX rule.
+pub const TZ: &str = "";
 
-// NTP server used to synchronise the system clock at boot.
-pub const NTP_SERVER: &str = "pool.ntp.org";
+// NTP servers used to synchronise the system clock, tried in order. If none
+// answers (e.g. UDP is blocked on a guest network), the clock is set from the
+// Date header of the Todoist API responses instead.
+pub const NTP_SERVERS: &[&str] = &["pool.ntp.org", "time.google.com", "time.cloudflare.com"];
+
+// The RTC keeps time through deep sleep, so NTP is only consulted every
+// NTP_RESYNC_HOURS, or sooner once the RTC's estimated drift could exceed
+// MAX_CLOCK_DRIFT_MS.
+pub const NTP_RESYNC_HOURS: u32 = 12;
+pub const MAX_CLOCK_DRIFT_MS: u32 = 30_000;
@@ -8,6 +8,10 @@ pub use header::Header;
 pub use popup::Popup;
 pub use task_list::TaskList;
 
+use embedded_graphics::prelude::Point;
+use u8g2_fonts::FontRenderer;
+use u8g2_fonts::types::VerticalPosition;
+
 use crate::display::DisplayBuffer;
 
 /// A drawable widget that tracks whether it needs re-rendering.
@@ -21,3 +25,16 @@ pub trait Control {
 
     fn render(&self, display: &mut DisplayBuffer<'_>);
 }
+
+/// Drawn in place of a character the font has no glyph for.
+const MISSING_GLYPH: char = '?';
+
+/// How far, in pixels, drawing `c` in `font` moves the pen. A character the
+/// font lacks is measured as [`MISSING_GLYPH`], which is drawn instead.
+fn glyph_advance(font: &FontRenderer, c: char) -> u32 {
+    font.get_rendered_dimensions(c, Point::zero(), VerticalPosition::Top)
+        .or_else(|_| {
+            font.get_rendered_dimensions(MISSING_GLYPH, Point::zero(), VerticalPosition::Top)
+        })
+        .map_or(0, |dimensions| dimensions.advance.x as u32)
+}
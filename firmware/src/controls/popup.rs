@@ -1,14 +1,22 @@
 //! A centred, full-screen status/error message.
+//!
+//! Long messages wrap onto several lines, each centred.
 
 use alloc::string::String;
 
 use embedded_graphics::prelude::*;
 use epd_waveshare::color::OctColor;
+use todoesp_core::{RichText, SpanStyle, layout};
 use u8g2_fonts::{FontRenderer, fonts, types::*};
 
-use super::Control;
+use super::{Control, MISSING_GLYPH, glyph_advance};
 use crate::display::DisplayBuffer;
 
+/// Space kept clear either side of the message, in pixels.
+const MESSAGE_MARGIN: u32 = 20;
+const MESSAGE_LINE_HEIGHT: i32 = 24;
+const MAX_MESSAGE_LINES: usize = 6;
+
 pub struct Popup {
     pub title: &'static str,
     pub message: String,
@@ -69,16 +77,44 @@ impl Control for Popup {
             )
             .ok();
 
-        FontRenderer::new::<fonts::u8g2_font_inb16_mf>()
-            .render_aligned(
-                self.message.as_str(),
-                display.bounding_box().center() + Point::new(0, 20),
-                VerticalPosition::Top,
-                HorizontalAlignment::Center,
-                FontColor::Transparent(self.message_color),
-                display,
-            )
-            .ok();
+        let message_font = FontRenderer::new::<fonts::u8g2_font_inb16_mf>();
+        let metrics = |c: char, _: SpanStyle| glyph_advance(&message_font, c);
+        let lines = layout::wrap(
+            &RichText::plain(self.message.as_str()),
+            display
+                .bounding_box()
+                .size
+                .width
+                .saturating_sub(2 * MESSAGE_MARGIN),
+            MAX_MESSAGE_LINES,
+            &metrics,
+        );
+        let font_color = FontColor::Transparent(self.message_color);
+        for (i, line) in lines.iter().enumerate() {
+            // Drawn a glyph at a time so that a character the font lacks
+            // becomes a `?` rather than losing the whole line.
+            let mut pen = display.bounding_box().center()
+                + Point::new(
+                    -(layout::width(line, &metrics) as i32 / 2),
+                    20 + i as i32 * MESSAGE_LINE_HEIGHT,
+                );
+            for c in line.spans().iter().flat_map(|span| span.text.chars()) {
+                let drawn = message_font
+                    .render(c, pen, VerticalPosition::Top, font_color, display)
+                    .or_else(|_| {
+                        message_font.render(
+                            MISSING_GLYPH,
+                            pen,
+                            VerticalPosition::Top,
+                            font_color,
+                            display,
+                        )
+                    });
+                if let Ok(dimensions) = drawn {
+                    pen.x += dimensions.advance.x;
+                }
+            }
+        }
     }
 
     fn is_dirty(&self) -> bool {
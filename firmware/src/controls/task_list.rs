@@ -4,10 +4,11 @@ use alloc::vec::Vec;
 
 use embedded_graphics::{geometry::*, primitives::*};
 use epd_waveshare::color::OctColor;
-use todoesp_core::TaskSnapshot;
+use todoesp_core::{RichText, SpanStyle, TaskSnapshot, TextMetrics, layout};
 use u8g2_fonts::FontRenderer;
+use u8g2_fonts::types::{FontColor, VerticalPosition};
 
-use super::Control;
+use super::{Control, MISSING_GLYPH, glyph_advance};
 use crate::display::DisplayBuffer;
 
 pub struct TaskList {
@@ -29,6 +30,15 @@ impl TaskList {
     }
 
     pub fn set_tasks<T>(&mut self, tasks: T) -> &mut Self
+    where
+        T: IntoIterator<Item = TaskSnapshot>,
+    {
+        self.set_tasks_of(tasks, 0)
+    }
+
+    /// Show `tasks`, the start of a list of `total` tasks, so that the ones
+    /// left out still count towards the "+ N more" line.
+    pub fn set_tasks_of<T>(&mut self, tasks: T, total: usize) -> &mut Self
     where
         T: IntoIterator<Item = TaskSnapshot>,
     {
@@ -40,6 +50,7 @@ impl TaskList {
                 new_tasks.push(task);
             }
         }
+        let count = count.max(total);
 
         self.dirty = self.dirty
             || self.count != count
@@ -78,20 +89,57 @@ impl Control for TaskList {
 
         const TASK_HEIGHT: u32 = 40;
         const CIRCLE_DIAMETER: i32 = 15;
+        const MAX_DESCRIPTION_LINES: usize = 3;
+        const DESCRIPTION_LINE_HEIGHT: u32 = 16;
 
         const TITLE_FONT_HEIGHT: i32 = 12;
-        let title_font = FontRenderer::new::<u8g2_fonts::fonts::u8g2_font_helvR12_tf>();
+        let title_style = RunStyle {
+            regular: FontRenderer::new::<u8g2_fonts::fonts::u8g2_font_helvR12_tf>(),
+            bold: FontRenderer::new::<u8g2_fonts::fonts::u8g2_font_helvB12_tf>(),
+            code: FontRenderer::new::<u8g2_fonts::fonts::u8g2_font_courR12_tf>(),
+            height: TITLE_FONT_HEIGHT,
+            color: OctColor::Black,
+            accent: OctColor::Blue,
+        };
 
         const INFO_FONT_HEIGHT: i32 = 9;
         let info_font = FontRenderer::new::<u8g2_fonts::fonts::u8g2_font_unifont_tf>();
+        let description_style = RunStyle {
+            regular: FontRenderer::new::<u8g2_fonts::fonts::u8g2_font_unifont_tf>(),
+            bold: FontRenderer::new::<u8g2_fonts::fonts::u8g2_font_helvB10_tf>(),
+            code: FontRenderer::new::<u8g2_fonts::fonts::u8g2_font_courR10_tf>(),
+            height: INFO_FONT_HEIGHT,
+            color: OctColor::Blue,
+            accent: OctColor::Black,
+        };
+
+        // The title and description fill the column right of the timeline
+        let text_left = 50 + CIRCLE_DIAMETER / 2 + 5;
+        let text_width = margin_box.size.width.saturating_sub(text_left as u32);
 
         let mut remaining = self.count;
-        for (i, task) in self.tasks.iter().enumerate() {
+        let mut top = 0;
+        for task in self.tasks.iter() {
+            // The description wraps, and the row grows to fit it
+            let description = task
+                .description
+                .as_ref()
+                .map_or_else(Vec::new, |description| {
+                    layout::wrap(
+                        description,
+                        text_width,
+                        MAX_DESCRIPTION_LINES,
+                        &description_style,
+                    )
+                });
+            let wrapped_lines = description.len().saturating_sub(1) as u32;
+            let task_height = TASK_HEIGHT + wrapped_lines * DESCRIPTION_LINE_HEIGHT;
+
             let task_box = Rectangle::new(
-                margin_box.anchor_point(AnchorPoint::TopLeft)
-                    + Point::new(0, i as i32 * TASK_HEIGHT as i32),
-                Size::new(margin_box.size.width, TASK_HEIGHT),
+                margin_box.anchor_point(AnchorPoint::TopLeft) + Point::new(0, top),
+                Size::new(margin_box.size.width, task_height),
             );
+            top += task_height as i32;
 
             // Don't render outside the margin box
             if task_box.anchor_point(AnchorPoint::BottomRight).y
@@ -117,34 +165,49 @@ impl Control for TaskList {
             )
             .ok();
 
+            // The title shares its line with the deadline
+            let deadline_width = task.deadline.as_deref().map_or(0, |deadline| {
+                info_font
+                    .get_rendered_dimensions(deadline, Point::zero(), VerticalPosition::Top)
+                    .map_or(0, |dimensions| dimensions.advance.x as u32 + 5)
+            });
+
             // Draw the task title
-            title_font
-                .render_aligned(
-                    task.title.as_str(),
-                    task_box.anchor_point(AnchorPoint::TopLeft)
-                        + Point::new(50 + CIRCLE_DIAMETER / 2 + 5, 0),
-                    u8g2_fonts::types::VerticalPosition::Top,
-                    u8g2_fonts::types::HorizontalAlignment::Left,
-                    u8g2_fonts::types::FontColor::Transparent(OctColor::Black),
-                    display,
-                )
-                .ok();
+            title_style.render(
+                &layout::fit(
+                    &task.title,
+                    text_width.saturating_sub(deadline_width),
+                    &title_style,
+                ),
+                task_box.anchor_point(AnchorPoint::TopLeft) + Point::new(text_left, 0),
+                display,
+            );
 
-            // Render the additional information text
-            if let Some(description) = task.description.as_deref() {
+            // Draw the deadline, if any, against the right-hand edge
+            if let Some(deadline) = task.deadline.as_deref() {
                 info_font
                     .render_aligned(
-                        description,
-                        task_box.anchor_point(AnchorPoint::TopLeft)
-                            + Point::new(50 + CIRCLE_DIAMETER / 2 + 5, TITLE_FONT_HEIGHT + 5),
+                        deadline,
+                        task_box.anchor_point(AnchorPoint::TopRight)
+                            + Point::new(0, (TITLE_FONT_HEIGHT - INFO_FONT_HEIGHT) / 2),
                         u8g2_fonts::types::VerticalPosition::Top,
-                        u8g2_fonts::types::HorizontalAlignment::Left,
-                        u8g2_fonts::types::FontColor::Transparent(OctColor::Blue),
+                        u8g2_fonts::types::HorizontalAlignment::Right,
+                        u8g2_fonts::types::FontColor::Transparent(task.deadline_color),
                         display,
                     )
                     .ok();
             }
 
+            // Render the additional information text
+            for (line, text) in description.iter().enumerate() {
+                let y = TITLE_FONT_HEIGHT + 5 + (line as u32 * DESCRIPTION_LINE_HEIGHT) as i32;
+                description_style.render(
+                    text,
+                    task_box.anchor_point(AnchorPoint::TopLeft) + Point::new(text_left, y),
+                    display,
+                );
+            }
+
             // Draw the "when" marker (done/todo/past/time)
             info_font
                 .render_aligned(
@@ -161,6 +224,14 @@ impl Control for TaskList {
                 )
                 .ok();
 
+            if task.recurring {
+                draw_repeat_glyph(
+                    display,
+                    task_box.top_left + Point::new(0, TITLE_FONT_HEIGHT + 5),
+                    OctColor::Blue,
+                );
+            }
+
             if let Some(duration) = task.duration.as_deref() {
                 info_font
                     .render_aligned(
@@ -199,3 +270,129 @@ impl Control for TaskList {
         self.dirty = false;
     }
 }
+
+/// The fonts and colours Markdown runs are drawn with: bold and code runs get
+/// their own font, links, mentions and italics the accent colour, and
+/// highlights a yellow background.
+struct RunStyle {
+    regular: FontRenderer,
+    bold: FontRenderer,
+    code: FontRenderer,
+    /// Height of the regular font's capitals, used to place the strikethrough
+    /// and link underline.
+    height: i32,
+    color: OctColor,
+    accent: OctColor,
+}
+
+impl RunStyle {
+    fn font(&self, style: SpanStyle) -> &FontRenderer {
+        if style.code {
+            &self.code
+        } else if style.bold {
+            &self.bold
+        } else {
+            &self.regular
+        }
+    }
+
+    fn color(&self, style: SpanStyle) -> OctColor {
+        if style.link || style.mention || style.italic {
+            self.accent
+        } else {
+            self.color
+        }
+    }
+
+    /// Draw `text` run by run, left to right from `top_left`.
+    ///
+    /// Glyphs are drawn one at a time, so a character the font lacks (emoji,
+    /// or CJK in the Latin-1 fonts) is drawn as [`MISSING_GLYPH`], just as
+    /// [`glyph_advance`] measures it, rather than losing the whole run.
+    fn render(&self, text: &RichText, top_left: Point, display: &mut DisplayBuffer<'_>) {
+        let mut position = top_left;
+        for span in text.spans() {
+            let color = self.color(span.style);
+            let font_color = if span.style.highlight {
+                FontColor::WithBackground {
+                    fg: color,
+                    bg: OctColor::Yellow,
+                }
+            } else {
+                FontColor::Transparent(color)
+            };
+            let font = self.font(span.style);
+
+            let mut width = 0;
+            for c in span.text.chars() {
+                let pen = position + Point::new(width, 0);
+                let drawn = font
+                    .render(c, pen, VerticalPosition::Top, font_color, display)
+                    .or_else(|_| {
+                        font.render(
+                            MISSING_GLYPH,
+                            pen,
+                            VerticalPosition::Top,
+                            font_color,
+                            display,
+                        )
+                    });
+                if let Ok(dimensions) = drawn {
+                    width += dimensions.advance.x;
+                }
+            }
+
+            let line_style = PrimitiveStyle::with_stroke(color, 1);
+            if span.style.strikethrough {
+                let y = self.height / 2;
+                Line::new(position + Point::new(0, y), position + Point::new(width, y))
+                    .draw_styled(&line_style, display)
+                    .ok();
+            }
+            if span.style.link {
+                let y = self.height + 2;
+                Line::new(position + Point::new(0, y), position + Point::new(width, y))
+                    .draw_styled(&line_style, display)
+                    .ok();
+            }
+
+            position.x += width;
+        }
+    }
+}
+
+impl TextMetrics for RunStyle {
+    fn advance(&self, c: char, style: SpanStyle) -> u32 {
+        glyph_advance(self.font(style), c)
+    }
+}
+
+/// Draw a small clockwise "repeat" arrow (↻) with its bounding box at
+/// `top_left`, marking a recurring task in the time column. The u8g2 text fonts
+/// we use have no such glyph, so it is built from primitives.
+fn draw_repeat_glyph(display: &mut DisplayBuffer<'_>, top_left: Point, color: OctColor) {
+    const DIAMETER: u32 = 9;
+    let style = PrimitiveStyle::with_stroke(color, 1);
+
+    // Three quarters of a circle, running clockwise from 3 o'clock round to
+    // 12 o'clock...
+    Arc::new(
+        top_left,
+        DIAMETER,
+        Angle::from_degrees(0.0),
+        Angle::from_degrees(270.0),
+    )
+    .draw_styled(&style, display)
+    .ok();
+
+    // ...capped by an arrowhead at 12 o'clock pointing along the direction of
+    // travel.
+    let tip = top_left + Point::new(DIAMETER as i32 / 2, 0);
+    Triangle::new(
+        tip + Point::new(0, -3),
+        tip + Point::new(0, 3),
+        tip + Point::new(3, 0),
+    )
+    .draw_styled(&PrimitiveStyle::with_fill(color), display)
+    .ok();
+}
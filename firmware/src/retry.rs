@@ -1,30 +1,64 @@
-//! A small async retry helper.
+//! A small async retry helper with exponential backoff.
 
 use core::fmt::Debug;
-use core::future::Future;
 
-/// Run `f`, retrying up to `retries` additional times on error.
-///
-/// Returns the first `Ok` value, or the final `Err` once all attempts are
-/// exhausted. Each failed attempt is logged at `warn` level.
-pub async fn retry<F, Fut, T, E>(mut f: F, retries: usize) -> Result<T, E>
-where
-    F: FnMut() -> Fut,
-    Fut: Future<Output = Result<T, E>>,
-    E: Debug,
-{
-    let mut attempt = 0;
-    loop {
-        match f().await {
-            Ok(v) => return Ok(v),
-            Err(e) if attempt == retries => return Err(e),
-            Err(e) => {
-                log::warn!(
-                    "Failed to execute operation (retry {} of {retries}): {e:?}",
-                    attempt + 1
-                );
-                attempt += 1;
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::rng::Rng;
+use todoesp_core::Backoff;
+
+/// How often, and how patiently, to retry a failing operation.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first.
+    pub max_attempts: u32,
+    /// The wait between attempts. Each wait is drawn at random (full jitter)
+    /// from the hardware RNG.
+    pub backoff: Backoff,
+    /// Give up rather than start a wait that would end this long after the
+    /// first attempt began.
+    pub budget: Duration,
+}
+
+impl RetryPolicy {
+    /// Run `f` until it succeeds, fails with an error `retryable` rejects, or
+    /// the policy's attempts or time budget run out.
+    ///
+    /// Returns the first `Ok` value, or the last `Err`. Each failed attempt is
+    /// logged at `warn` level.
+    pub async fn run<T, E: Debug>(
+        &self,
+        mut f: impl AsyncFnMut() -> Result<T, E>,
+        retryable: impl Fn(&E) -> bool,
+    ) -> Result<T, E> {
+        let start = Instant::now();
+        let mut rng = Rng::new();
+        let mut failures = 0;
+        loop {
+            let e = match f().await {
+                Ok(v) => return Ok(v),
+                Err(e) => e,
+            };
+            failures += 1;
+            if !retryable(&e) {
+                log::warn!("Operation failed permanently: {e:?}");
+                return Err(e);
+            }
+            if failures == self.max_attempts {
+                log::warn!("Operation failed on the last of {failures} attempts: {e:?}");
+                return Err(e);
+            }
+
+            let delay = Duration::from_micros(self.backoff.delay_micros(failures, rng.random()));
+            if start.elapsed() + delay > self.budget {
+                log::warn!("Operation failed and its retry budget is spent: {e:?}");
+                return Err(e);
             }
+            log::warn!(
+                "Failed to execute operation (retry {failures} of {} in {} ms): {e:?}",
+                self.max_attempts - 1,
+                delay.as_millis()
+            );
+            Timer::after(delay).await;
         }
     }
 }
@@ -1,21 +1,20 @@
-//! Minimal SNTP (NTP) client over UDP for one-shot clock synchronisation.
+//! SNTP (NTP) transport over UDP for one-shot clock synchronisation.
 //!
 //! `chrono::Local` is unavailable in `no_std`, so the device learns the current
-//! wall-clock time once at boot via NTP and thereafter advances it using the
-//! monotonic embassy timer plus the configured fixed UTC offset.
+//! wall-clock time once per wake via NTP and thereafter advances it using the
+//! monotonic embassy timer. Packet encoding and reply validation live in
+//! [`todoesp_core::ntp`]; this module only moves the bytes.
 
 use embassy_net::dns::DnsQueryType;
 use embassy_net::udp::{PacketMetadata, UdpSocket};
 use embassy_net::{IpEndpoint, Stack};
-use embassy_time::{Duration, with_timeout};
-use static_cell::StaticCell;
+use embassy_time::{Duration, Instant, with_timeout};
+use todoesp_core::ntp::{self, NtpError, NtpSample, NtpTimestamp};
 
-/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch (1970-01-01).
-const NTP_TO_UNIX: i64 = 2_208_988_800;
 /// Local UDP port the client binds to.
 const LOCAL_PORT: u16 = 50123;
-/// Standard NTP server port.
-const NTP_PORT: u16 = 123;
+/// How long to wait for a reply from a single server address.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
 pub enum SntpError {
@@ -29,57 +28,83 @@ pub enum SntpError {
     Send,
     /// No response arrived within the timeout.
     Timeout,
-    /// The response was too short to contain a transmit timestamp.
-    ShortResponse,
+    /// The server's reply was rejected.
+    Reply(NtpError),
 }
 
-/// Query `server` over NTP and return the current time as a Unix timestamp
-/// (seconds since 1970-01-01 UTC).
-pub async fn sync_unix_time(stack: Stack<'static>, server: &str) -> Result<i64, SntpError> {
+impl SntpError {
+    /// Whether asking the same server again may help. A kiss-of-death reply
+    /// is the server telling us to go away, so it is not retried.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, Self::Reply(NtpError::KissOfDeath(_)))
+    }
+}
+
+/// The monotonic clock the NTP offset is measured against.
+///
+/// Pass the result of [`sync`] to [`NtpSample::unix_micros`] with a later
+/// reading of this clock to get the current Unix time.
+pub fn client_micros() -> i64 {
+    Instant::now().as_micros() as i64
+}
+
+/// Query `server` over NTP and return the offset between [`client_micros`]
+/// and Unix time, compensated for the round-trip delay.
+///
+/// The network stack is IPv4-only, so `server` is resolved to A records; each
+/// address is tried in turn until one gives a valid reply.
+pub async fn sync(stack: Stack<'static>, server: &str) -> Result<NtpSample, SntpError> {
     let addresses = stack
         .dns_query(server, DnsQueryType::A)
         .await
         .map_err(|_| SntpError::Dns)?;
-    let address = addresses.into_iter().next().ok_or(SntpError::NoAddress)?;
 
-    static RX_META: StaticCell<[PacketMetadata; 4]> = StaticCell::new();
-    static RX_BUF: StaticCell<[u8; 256]> = StaticCell::new();
-    static TX_META: StaticCell<[PacketMetadata; 4]> = StaticCell::new();
-    static TX_BUF: StaticCell<[u8; 256]> = StaticCell::new();
-
-    let mut socket = UdpSocket::new(
-        stack,
-        RX_META.init([PacketMetadata::EMPTY; 4]),
-        RX_BUF.init([0; 256]),
-        TX_META.init([PacketMetadata::EMPTY; 4]),
-        TX_BUF.init([0; 256]),
-    );
+    // Small enough for the task's stack, and unlike `StaticCell` storage they
+    // can be set up again for every attempt.
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buf = [0u8; 256];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buf = [0u8; 256];
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buf, &mut tx_meta, &mut tx_buf);
     socket.bind(LOCAL_PORT).map_err(|_| SntpError::Bind)?;
 
-    // NTPv3 client request: LI = 0, VN = 3, Mode = 3.
-    let mut request = [0u8; 48];
-    request[0] = 0x1B;
+    let mut last_error = SntpError::NoAddress;
+    for address in addresses {
+        let endpoint = IpEndpoint::new(address, ntp::NTP_PORT);
+        match query(&mut socket, endpoint).await {
+            Ok(sample) => return Ok(sample),
+            Err(e) => {
+                log::warn!("NTP server {server} ({address}) failed: {e:?}");
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
+}
 
+/// Send one request to `endpoint` and wait for its reply.
+async fn query(socket: &mut UdpSocket<'_>, endpoint: IpEndpoint) -> Result<NtpSample, SntpError> {
+    let originate = NtpTimestamp::from_unix_micros(client_micros());
     socket
-        .send_to(&request, IpEndpoint::new(address, NTP_PORT))
+        .send_to(&ntp::request(originate), endpoint)
         .await
         .map_err(|_| SntpError::Send)?;
 
-    // The transmit timestamp (server's idea of "now") is the 4-byte big-endian
-    // seconds field at offset 40 in the 48-byte NTP packet.
-    let seconds = with_timeout(
-        Duration::from_secs(10),
-        socket.recv_from_with(|buf, _meta| {
-            if buf.len() < 44 {
-                None
-            } else {
-                Some(u32::from_be_bytes([buf[40], buf[41], buf[42], buf[43]]))
+    // Datagrams from anyone but the server are dropped without a look; the
+    // core validation then checks the reply echoes our request.
+    with_timeout(REPLY_TIMEOUT, async {
+        loop {
+            let reply = socket
+                .recv_from_with(|buf, meta| {
+                    let received = NtpTimestamp::from_unix_micros(client_micros());
+                    (meta.endpoint == endpoint).then(|| ntp::parse_reply(buf, originate, received))
+                })
+                .await;
+            if let Some(reply) = reply {
+                return reply.map_err(SntpError::Reply);
             }
-        }),
-    )
+        }
+    })
     .await
-    .map_err(|_| SntpError::Timeout)?;
-
-    let ntp_seconds = seconds.ok_or(SntpError::ShortResponse)?;
-    Ok(ntp_seconds as i64 - NTP_TO_UNIX)
+    .map_err(|_| SntpError::Timeout)?
 }
@@ -0,0 +1,93 @@
+//! The last successfully fetched task list, kept in flash so it can still be
+//! shown while offline, even after a power cycle.
+//!
+//! The list lives in the `tasks` data partition (see `partitions.csv`) in the
+//! checksummed [`TaskRecord`] format from `todoesp-core`, so an erased, torn or
+//! outdated partition simply reads back as "no tasks".
+
+use alloc::vec;
+
+use embedded_storage::{ReadStorage, Storage};
+use esp_bootloader_esp_idf::partitions::{self, PARTITION_TABLE_MAX_LEN};
+use esp_storage::FlashStorage;
+use todoesp_core::{DecodeError, TaskRecord, decode_record, encode_record};
+
+/// Label of the data partition in `partitions.csv`.
+const PARTITION_LABEL: &str = "tasks";
+/// Largest record read or written. Longer task lists are cut short.
+const RECORD_SIZE: usize = 8_192;
+/// An unchanged task list is only rewritten once it is this old (in seconds),
+/// to spare the flash a write on every fetch.
+const REWRITE_AFTER_SECS: i64 = 3_600;
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// The partition table could not be read, or the flash access failed.
+    Flash(partitions::Error),
+    /// The partition table has no `tasks` partition.
+    NoPartition,
+    /// The partition doesn't hold a valid record.
+    Record(DecodeError),
+}
+
+pub struct TaskStore<'d> {
+    flash: FlashStorage<'d>,
+}
+
+impl<'d> TaskStore<'d> {
+    pub fn new(flash: esp_hal::peripherals::FLASH<'d>) -> Self {
+        Self {
+            flash: FlashStorage::new(flash),
+        }
+    }
+
+    /// Read the stored task list.
+    pub fn load(&mut self) -> Result<TaskRecord, StorageError> {
+        let mut bytes = vec![0u8; RECORD_SIZE];
+        self.with_partition(|region| region.read(0, &mut bytes))?;
+        decode_record(&bytes).map_err(StorageError::Record)
+    }
+
+    /// Replace the stored task list, unless it holds the same tasks and was
+    /// written recently.
+    pub fn store(&mut self, record: &TaskRecord) -> Result<(), StorageError> {
+        let mut bytes = vec![0u8; RECORD_SIZE];
+        let len = encode_record(record, &mut bytes);
+
+        if let Ok(stored) = self.load()
+            && record.fetched_unix - stored.fetched_unix < REWRITE_AFTER_SECS
+        {
+            // Re-encode the stored tasks with the new fetch time, so only
+            // their content is compared.
+            let mut previous = vec![0u8; RECORD_SIZE];
+            let stored = TaskRecord {
+                fetched_unix: record.fetched_unix,
+                ..stored
+            };
+            let previous_len = encode_record(&stored, &mut previous);
+            if previous[..previous_len] == bytes[..len] {
+                return Ok(());
+            }
+        }
+
+        self.with_partition(|region| region.write(0, &bytes[..len]))
+    }
+
+    /// Run `f` on the `tasks` partition.
+    fn with_partition<T>(
+        &mut self,
+        f: impl FnOnce(
+            &mut partitions::FlashRegion<'_, FlashStorage<'d>>,
+        ) -> Result<T, partitions::Error>,
+    ) -> Result<T, StorageError> {
+        let mut table = [0u8; PARTITION_TABLE_MAX_LEN];
+        let table = partitions::read_partition_table(&mut self.flash, &mut table)
+            .map_err(StorageError::Flash)?;
+        let entry = table
+            .iter()
+            .find(|entry| entry.label_as_str() == PARTITION_LABEL)
+            .ok_or(StorageError::NoPartition)?;
+        let mut region = entry.as_embedded_storage(&mut self.flash);
+        f(&mut region).map_err(StorageError::Flash)
+    }
+}
@@ -1,4 +1,4 @@
-//! HTTPS client for the Todoist filtered-tasks API.
+//! HTTPS client for the Todoist filtered-tasks and user-profile APIs.
 //!
 //! ## Security note
 //!
@@ -8,6 +8,8 @@
 //! ever talks to `api.todoist.com` over a WPA2 network. Treat the link as
 //! confidential-but-unauthenticated.
 
+use core::cell::Cell;
+
 use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
@@ -18,7 +20,12 @@ use embassy_net::tcp::client::{TcpClient, TcpClientState};
 use embedded_io_async::Read;
 use reqwless::client::{HttpClient, TlsConfig, TlsVerify};
 use reqwless::request::{Method, RequestBuilder};
-use todoesp_core::{Task, TaskStreamParser};
+use todoesp_core::{
+    FetchFailure, ParseError, Task, TaskStreamParser, UserProfile, parse_http_date,
+    parse_retry_after, parse_user_profile,
+};
+
+use crate::sntp;
 
 /// Number of concurrent TCP connections / per-connection buffer sizes used by
 /// the reqwless client.
@@ -28,18 +35,45 @@ pub type ClientState = TcpClientState<1, 4096, 4096>;
 pub enum TodoistError {
     /// The HTTP request failed (connection, TLS handshake, DNS, ...).
     Request,
-    /// The server returned a non-2xx status code.
-    Status(u16),
-    /// The response body could not be read.
+    /// The server returned a non-2xx status code, with the delay in seconds
+    /// from its `Retry-After` header (if any).
+    Status {
+        status: u16,
+        retry_after: Option<u64>,
+    },
+    /// The response body could not be read, or ended before the JSON did.
     Body,
     /// The response body was not valid Todoist JSON.
-    Parse,
+    Parse(ParseError),
+}
+
+impl TodoistError {
+    /// What the failure means for the device; see [`FetchFailure`].
+    pub fn classify(&self) -> FetchFailure {
+        match *self {
+            Self::Request | Self::Body => FetchFailure::Network,
+            Self::Status {
+                status,
+                retry_after,
+            } => FetchFailure::from_status(status, retry_after).unwrap_or(FetchFailure::Network),
+            Self::Parse(_) => FetchFailure::Parse,
+        }
+    }
 }
 
+/// Number of tasks requested per page (the maximum the API accepts).
+const PAGE_SIZE: usize = 200;
+
+/// Upper bound on the buffered user-profile response. The profile is a single
+/// object of a few kilobytes; anything larger is treated as a bad response.
+const MAX_PROFILE_SIZE: usize = 16_384;
+
 pub struct TodoistClient {
     api_key: &'static str,
     filter: &'static str,
     state: &'static ClientState,
+    max_pages: usize,
+    server_clock: Cell<Option<i64>>,
 }
 
 impl TodoistClient {
@@ -48,10 +82,24 @@ impl TodoistClient {
             api_key,
             filter,
             state,
+            max_pages: 1,
+            server_clock: Cell::new(None),
         }
     }
 
-    /// Fetch and parse the tasks matching the configured filter.
+    /// Set the maximum number of result pages (of up to 200 tasks each)
+    /// [`get_tasks`](Self::get_tasks) will follow before giving up on the rest.
+    ///
+    /// Every task is kept in memory until the list is sorted, so this bounds
+    /// the heap used by a very busy account.
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages.max(1);
+        self
+    }
+
+    /// Fetch and parse the tasks matching the configured filter, following the
+    /// API's `next_cursor` pagination up to the configured page limit. The
+    /// tasks are in no particular order; see [`todoesp_core::sort_tasks`].
     ///
     /// `seed` is used to seed the TLS RNG and should be different on each call.
     /// `tls_read`/`tls_write` are the TLS record buffers (each must be at least
@@ -66,15 +114,172 @@ impl TodoistClient {
         tls_write: &mut [u8],
         rx_buf: &mut [u8],
     ) -> Result<Vec<Task>, TodoistError> {
+        let mut tasks: Vec<Task> = Vec::new();
+        let mut cursor: Option<String> = None;
+        for page in 0..self.max_pages {
+            // Each page is a fresh TLS session, so it needs its own RNG seed.
+            let page_seed = seed ^ (page as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            cursor = self
+                .get_page(
+                    stack,
+                    page_seed,
+                    tls_read,
+                    tls_write,
+                    rx_buf,
+                    cursor.as_deref(),
+                    &mut tasks,
+                )
+                .await?;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        if cursor.is_some() {
+            log::warn!(
+                "Stopped after {} pages of Todoist tasks; the remaining tasks were dropped",
+                self.max_pages
+            );
+        }
+
+        Ok(tasks)
+    }
+
+    /// The offset from [`sntp::client_micros`] to Unix time according to the
+    /// `Date` header of the most recent response, if any.
+    ///
+    /// This is a fallback time source for networks that block NTP; it is only
+    /// accurate to about a second.
+    pub fn server_clock(&self) -> Option<i64> {
+        self.server_clock.get()
+    }
+
+    /// Remember the server's clock from a response's `Date` header.
+    fn note_date<'h>(&self, headers: impl Iterator<Item = (&'h str, &'h [u8])>) {
+        let received = sntp::client_micros();
+        let date = headers
+            .filter(|(name, _)| name.eq_ignore_ascii_case("date"))
+            .find_map(|(_, value)| core::str::from_utf8(value).ok().and_then(parse_http_date));
+        if let Some(unix) = date {
+            // The header is truncated to whole seconds, so assume the middle
+            // of that second.
+            self.server_clock
+                .set(Some(unix * 1_000_000 + 500_000 - received));
+        }
+    }
+
+    /// The delay requested by a response's `Retry-After` header, in seconds.
+    ///
+    /// A date is measured against the server's own clock (from the `Date`
+    /// header of the same response), so the device's clock doesn't matter. If
+    /// no `Date` header has been seen, only a number of seconds is used.
+    fn retry_after<'h>(&self, headers: impl Iterator<Item = (&'h str, &'h [u8])>) -> Option<u64> {
+        let now_unix = self
+            .server_clock
+            .get()
+            .map(|clock| (sntp::client_micros() + clock).div_euclid(1_000_000));
+        headers
+            .filter(|(name, _)| name.eq_ignore_ascii_case("retry-after"))
+            .find_map(|(_, value)| {
+                core::str::from_utf8(value)
+                    .ok()
+                    .and_then(|value| parse_retry_after(value, now_unix))
+            })
+    }
+
+    /// Fetch the account's user profile (used for its timezone).
+    ///
+    /// The buffers have the same requirements as for
+    /// [`get_tasks`](Self::get_tasks). The response is small, so unlike the
+    /// task list it is buffered whole (up to 16 KiB) before parsing.
+    pub async fn get_user(
+        &self,
+        stack: Stack<'static>,
+        seed: u64,
+        tls_read: &mut [u8],
+        tls_write: &mut [u8],
+        rx_buf: &mut [u8],
+    ) -> Result<UserProfile, TodoistError> {
+        let tcp = TcpClient::new(stack, self.state);
+        let dns = DnsSocket::new(stack);
+        let tls = TlsConfig::new(seed, tls_read, tls_write, TlsVerify::None);
+        let mut client = HttpClient::new_with_tls(&tcp, &dns, tls);
+
+        let auth = format!("Bearer {}", self.api_key);
+        let headers = [("Authorization", auth.as_str())];
+
+        log::info!("Requesting the user profile from Todoist API");
+        let mut request = client
+            .request(Method::GET, "https://api.todoist.com/api/v1/user")
+            .await
+            .map_err(|_| TodoistError::Request)?
+            .headers(&headers);
+
+        let response = request
+            .send(rx_buf)
+            .await
+            .map_err(|_| TodoistError::Request)?;
+
+        self.note_date(response.headers());
+        let status = response.status;
+        if !status.is_successful() {
+            log::error!("Unexpected status code from Todoist API: HTTP {}", status.0);
+            return Err(TodoistError::Status {
+                status: status.0,
+                retry_after: self.retry_after(response.headers()),
+            });
+        }
+
+        let mut body = Vec::new();
+        let mut reader = response.body().reader();
+        let mut chunk = [0u8; 512];
+        loop {
+            let read = reader
+                .read(&mut chunk)
+                .await
+                .map_err(|_| TodoistError::Body)?;
+            if read == 0 {
+                break;
+            }
+            if body.len() + read > MAX_PROFILE_SIZE {
+                log::error!("Todoist user profile exceeds {MAX_PROFILE_SIZE} bytes");
+                return Err(TodoistError::Body);
+            }
+            body.extend_from_slice(&chunk[..read]);
+        }
+
+        parse_user_profile(&body).map_err(|e| {
+            log::error!("Failed to parse Todoist user JSON: {e}");
+            TodoistError::Parse(e)
+        })
+    }
+
+    /// Fetch a single page of tasks, appending them to `tasks` and returning
+    /// the cursor for the following page (if there is one).
+    #[allow(clippy::too_many_arguments)]
+    async fn get_page(
+        &self,
+        stack: Stack<'static>,
+        seed: u64,
+        tls_read: &mut [u8],
+        tls_write: &mut [u8],
+        rx_buf: &mut [u8],
+        cursor: Option<&str>,
+        tasks: &mut Vec<Task>,
+    ) -> Result<Option<String>, TodoistError> {
         let tcp = TcpClient::new(stack, self.state);
         let dns = DnsSocket::new(stack);
         let tls = TlsConfig::new(seed, tls_read, tls_write, TlsVerify::None);
         let mut client = HttpClient::new_with_tls(&tcp, &dns, tls);
 
-        let url = format!(
-            "https://api.todoist.com/api/v1/tasks/filter?limit=200&query={}",
+        let mut url = format!(
+            "https://api.todoist.com/api/v1/tasks/filter?limit={PAGE_SIZE}&query={}",
             percent_encode(self.filter)
         );
+        if let Some(cursor) = cursor {
+            url.push_str("&cursor=");
+            url.push_str(&percent_encode(cursor));
+        }
         let auth = format!("Bearer {}", self.api_key);
         let headers = [("Authorization", auth.as_str())];
 
@@ -90,6 +295,7 @@ impl TodoistClient {
             .await
             .map_err(|_| TodoistError::Request)?;
 
+        self.note_date(response.headers());
         let status = response.status;
         let content_length = response.content_length;
         log::info!(
@@ -99,7 +305,10 @@ impl TodoistClient {
         );
         if !status.is_successful() {
             log::error!("Unexpected status code from Todoist API: HTTP {}", status.0);
-            return Err(TodoistError::Status(status.0));
+            return Err(TodoistError::Status {
+                status: status.0,
+                retry_after: self.retry_after(response.headers()),
+            });
         }
 
         // Parse the response incrementally as it streams off the network. The
@@ -108,10 +317,10 @@ impl TodoistClient {
         // failure; the streaming parser only ever holds a single task object at
         // a time.
         let mut parser = TaskStreamParser::new();
-        let mut tasks: Vec<Task> = Vec::new();
         let mut reader = response.body().reader();
         let mut chunk = [0u8; 512];
         let mut total = 0usize;
+        let before = tasks.len();
         loop {
             let read = reader
                 .read(&mut chunk)
@@ -121,20 +330,24 @@ impl TodoistClient {
                 break;
             }
             total += read;
-            parser.feed(&chunk[..read], &mut tasks).map_err(|e| {
-                log::error!("Failed to parse Todoist task JSON: {e:?}");
-                TodoistError::Parse
+            parser.feed(&chunk[..read], tasks).map_err(|e| {
+                log::error!("Failed to parse Todoist task JSON: {e}");
+                TodoistError::Parse(e)
             })?;
         }
+        // A body cut short by the network is only detectable once it ends.
+        parser.finish().map_err(|e| {
+            log::error!("Todoist response ended early: {e}");
+            TodoistError::Body
+        })?;
 
-        tasks.sort();
         log::info!(
             "Parsed {} tasks from {} bytes of Todoist response",
-            tasks.len(),
+            tasks.len() - before,
             total
         );
 
-        Ok(tasks)
+        Ok(parser.into_next_cursor())
     }
 }
 
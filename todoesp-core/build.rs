@@ -0,0 +1,61 @@
+//! Generates the embedded timezone table when the `tzdb` feature is enabled.
+//!
+//! `tzdata/zones.tsv` maps IANA zone names to POSIX TZ rules. Many zones share
+//! a rule, so the table is emitted as a list of distinct rules plus a sorted
+//! list of `(name, rule index)` pairs that `time::PosixTz::from_iana` searches.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const SOURCE: &str = "tzdata/zones.tsv";
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={SOURCE}");
+    if env::var_os("CARGO_FEATURE_TZDB").is_none() {
+        return;
+    }
+
+    let source = fs::read_to_string(SOURCE).expect("read the zone table");
+    let mut zones = BTreeMap::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, rule) = line
+            .split_once('\t')
+            .unwrap_or_else(|| panic!("{SOURCE}:{}: expected `name<TAB>rule`", number + 1));
+        if zones.insert(name, rule).is_some() {
+            panic!("{SOURCE}:{}: duplicate zone `{name}`", number + 1);
+        }
+    }
+
+    let mut rules: Vec<&str> = zones.values().copied().collect();
+    rules.sort_unstable();
+    rules.dedup();
+
+    let mut out = String::new();
+    writeln!(out, "pub(super) static RULES: [&str; {}] = [", rules.len()).unwrap();
+    for rule in &rules {
+        writeln!(out, "    {rule:?},").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(
+        out,
+        "pub(super) static ZONES: [(&str, u16); {}] = [",
+        zones.len()
+    )
+    .unwrap();
+    for (name, rule) in &zones {
+        let index = rules.binary_search(rule).unwrap();
+        writeln!(out, "    ({name:?}, {index}),").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    let dest = Path::new(&env::var_os("OUT_DIR").unwrap()).join("zones.rs");
+    fs::write(dest, out).expect("write the generated zone table");
+}
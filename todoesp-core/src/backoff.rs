@@ -0,0 +1,73 @@
+//! Exponential backoff with "full jitter".
+//!
+//! After each consecutive failure the longest wait doubles, up to a cap, and
+//! the actual wait is drawn uniformly between zero and that ceiling. Spreading
+//! retries out like this stops a fleet of devices that lost the same router (or
+//! hit the same Todoist outage) from retrying in lockstep. The random numbers
+//! come from the caller so the schedule itself stays deterministic and
+//! testable.
+
+/// An exponential backoff schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// The ceiling after the first failure, in microseconds.
+    pub base_micros: u64,
+    /// The largest ceiling, however many failures there have been.
+    pub max_micros: u64,
+}
+
+impl Backoff {
+    /// The longest wait after `failures` consecutive failures (counting from
+    /// one): `base_micros` doubled for each failure after the first, capped at
+    /// `max_micros`. Zero failures means no wait at all.
+    pub fn ceiling_micros(&self, failures: u32) -> u64 {
+        let Some(doublings) = failures.checked_sub(1) else {
+            return 0;
+        };
+        self.base_micros
+            .checked_shl(doublings)
+            .filter(|ceiling| ceiling >> doublings == self.base_micros)
+            .unwrap_or(u64::MAX)
+            .min(self.max_micros)
+    }
+
+    /// The wait after `failures` consecutive failures: uniform between zero
+    /// and [`ceiling_micros`](Self::ceiling_micros), chosen by `random`.
+    pub fn delay_micros(&self, failures: u32, random: u32) -> u64 {
+        let ceiling = u128::from(self.ceiling_micros(failures));
+        ((ceiling * u128::from(random)) >> 32) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000;
+
+    const BACKOFF: Backoff = Backoff {
+        base_micros: SECOND,
+        max_micros: 30 * SECOND,
+    };
+
+    #[test]
+    fn ceiling_doubles_up_to_the_cap() {
+        assert_eq!(BACKOFF.ceiling_micros(0), 0);
+        assert_eq!(BACKOFF.ceiling_micros(1), SECOND);
+        assert_eq!(BACKOFF.ceiling_micros(2), 2 * SECOND);
+        assert_eq!(BACKOFF.ceiling_micros(5), 16 * SECOND);
+        assert_eq!(BACKOFF.ceiling_micros(6), 30 * SECOND);
+        // Far beyond the point where the doubling would overflow.
+        assert_eq!(BACKOFF.ceiling_micros(70), 30 * SECOND);
+        assert_eq!(BACKOFF.ceiling_micros(u32::MAX), 30 * SECOND);
+    }
+
+    #[test]
+    fn jitter_spans_zero_to_the_ceiling() {
+        assert_eq!(BACKOFF.delay_micros(3, 0), 0);
+        assert_eq!(BACKOFF.delay_micros(3, 1 << 31), 2 * SECOND);
+        let longest = BACKOFF.delay_micros(3, u32::MAX);
+        assert!((4 * SECOND - 1..4 * SECOND).contains(&longest), "{longest}");
+        assert_eq!(BACKOFF.delay_micros(0, u32::MAX), 0);
+    }
+}
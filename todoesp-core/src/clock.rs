@@ -0,0 +1,219 @@
+//! Wall-clock time kept across deep sleep by the RTC.
+//!
+//! The RTC slow clock keeps counting while the chip sleeps, but it is an RC
+//! oscillator whose rate is only roughly known. [`RtcClock`] pairs an RTC
+//! reading with the Unix time it corresponded to at the last NTP sync, learns
+//! the RTC's rate error from successive syncs, and bounds how far its estimate
+//! may have wandered so the firmware knows when a fresh sync is due.
+
+/// Assumed worst-case RTC rate error before it has been measured, in parts per
+/// million.
+pub const UNCALIBRATED_PPM: u64 = 500;
+/// Assumed worst-case residual rate error once it has been measured.
+pub const CALIBRATED_PPM: u64 = 50;
+/// Shortest interval between syncs that is used to measure the rate error;
+/// over shorter spans NTP jitter dominates the measurement.
+pub const MIN_CALIBRATION_MICROS: u64 = 30 * 60 * 1_000_000;
+/// Largest rate correction accepted, in parts per billion. Anything beyond
+/// this is treated as a bad sample rather than a real drift.
+const MAX_DRIFT_PPB: i64 = 2_000_000;
+
+/// When to resynchronise the clock over NTP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncPolicy {
+    /// Resync at least this often, however small the estimated error.
+    pub max_interval_micros: u64,
+    /// Resync once the estimated error could exceed this.
+    pub max_error_micros: u64,
+}
+
+/// The Unix time at an RTC reading, plus the RTC's measured rate error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtcClock {
+    unix_micros: i64,
+    rtc_micros: u64,
+    drift_ppb: Option<i32>,
+}
+
+impl RtcClock {
+    /// Anchor a clock whose rate error is not yet known.
+    pub fn new(unix_micros: i64, rtc_micros: u64) -> Self {
+        Self::from_parts(unix_micros, rtc_micros, None)
+    }
+
+    /// Rebuild a clock from the values returned by its accessors.
+    pub fn from_parts(unix_micros: i64, rtc_micros: u64, drift_ppb: Option<i32>) -> Self {
+        Self {
+            unix_micros,
+            rtc_micros,
+            drift_ppb,
+        }
+    }
+
+    /// Unix time at the last sync, in microseconds.
+    pub fn unix_micros(&self) -> i64 {
+        self.unix_micros
+    }
+
+    /// RTC reading at the last sync, in microseconds.
+    pub fn rtc_micros(&self) -> u64 {
+        self.rtc_micros
+    }
+
+    /// How much faster real time runs than the RTC, in parts per billion, if
+    /// it has been measured.
+    pub fn drift_ppb(&self) -> Option<i32> {
+        self.drift_ppb
+    }
+
+    /// Estimate the Unix time (in microseconds) at an RTC reading.
+    ///
+    /// Returns `None` if the RTC reads earlier than at the last sync, which
+    /// means it was reset (e.g. by a power cycle) and the anchor is useless.
+    pub fn now(&self, rtc_micros: u64) -> Option<i64> {
+        let elapsed = i128::from(rtc_micros.checked_sub(self.rtc_micros)?);
+        let drift = i128::from(self.drift_ppb.unwrap_or(0));
+        let corrected = elapsed + elapsed * drift / 1_000_000_000;
+        i64::try_from(i128::from(self.unix_micros) + corrected).ok()
+    }
+
+    /// The worst-case error of [`now`](Self::now) at an RTC reading.
+    pub fn error_bound_micros(&self, rtc_micros: u64) -> Option<u64> {
+        let elapsed = rtc_micros.checked_sub(self.rtc_micros)?;
+        let ppm = match self.drift_ppb {
+            Some(_) => CALIBRATED_PPM,
+            None => UNCALIBRATED_PPM,
+        };
+        Some(elapsed / 1_000_000 * ppm + elapsed % 1_000_000 * ppm / 1_000_000)
+    }
+
+    /// Whether the clock should be resynchronised at an RTC reading.
+    pub fn needs_sync(&self, rtc_micros: u64, policy: &SyncPolicy) -> bool {
+        match self.error_bound_micros(rtc_micros) {
+            Some(error) => {
+                rtc_micros - self.rtc_micros >= policy.max_interval_micros
+                    || error >= policy.max_error_micros
+            }
+            None => true,
+        }
+    }
+
+    /// Re-anchor the clock at a fresh sync.
+    ///
+    /// The gap between the estimate and the synced time reveals how far the
+    /// RTC's rate is off, which refines the correction applied from now on.
+    pub fn resync(&self, unix_micros: i64, rtc_micros: u64) -> Self {
+        let (Some(estimate), Some(elapsed)) = (
+            self.now(rtc_micros),
+            rtc_micros.checked_sub(self.rtc_micros),
+        ) else {
+            return Self::new(unix_micros, rtc_micros);
+        };
+        if elapsed < MIN_CALIBRATION_MICROS {
+            return Self::from_parts(unix_micros, rtc_micros, self.drift_ppb);
+        }
+
+        let error = i128::from(unix_micros) - i128::from(estimate);
+        let correction = error * 1_000_000_000 / i128::from(elapsed);
+        let drift = i128::from(self.drift_ppb.unwrap_or(0)) + correction;
+        let drift_ppb = if drift.unsigned_abs() <= MAX_DRIFT_PPB as u128 {
+            Some(drift as i32)
+        } else {
+            self.drift_ppb
+        };
+        Self::from_parts(unix_micros, rtc_micros, drift_ppb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000;
+    const HOUR: u64 = 3_600 * SECOND;
+    /// 2024-01-01T00:00:00Z.
+    const JAN_2024: i64 = 1_704_067_200_000_000;
+
+    const POLICY: SyncPolicy = SyncPolicy {
+        max_interval_micros: 12 * HOUR,
+        max_error_micros: 30 * SECOND,
+    };
+
+    /// An RTC running 100 ppm slow: the reading after `real` microseconds.
+    fn slow_rtc(real: u64) -> u64 {
+        real - real / 10_000
+    }
+
+    #[test]
+    fn advances_with_the_rtc() {
+        let clock = RtcClock::new(JAN_2024, 5 * SECOND);
+        assert_eq!(clock.now(5 * SECOND), Some(JAN_2024));
+        assert_eq!(clock.now(HOUR + 5 * SECOND), Some(JAN_2024 + HOUR as i64));
+    }
+
+    #[test]
+    fn rtc_reset_invalidates_the_anchor() {
+        let clock = RtcClock::new(JAN_2024, HOUR);
+        assert_eq!(clock.now(SECOND), None);
+        assert!(clock.needs_sync(SECOND, &POLICY));
+        assert_eq!(
+            clock.resync(JAN_2024, SECOND),
+            RtcClock::new(JAN_2024, SECOND)
+        );
+    }
+
+    #[test]
+    fn learns_the_rtc_rate_from_successive_syncs() {
+        let clock = RtcClock::new(JAN_2024, 0);
+
+        // After an hour the uncorrected estimate is 360 ms behind.
+        let rtc = slow_rtc(HOUR);
+        assert_eq!(clock.now(rtc), Some(JAN_2024 + HOUR as i64 - 360_000));
+        let clock = clock.resync(JAN_2024 + HOUR as i64, rtc);
+        let drift = clock.drift_ppb().unwrap();
+        assert!((100_000..100_020).contains(&drift), "{drift}");
+
+        // The next hour is predicted to within a millisecond.
+        let estimate = clock.now(slow_rtc(2 * HOUR)).unwrap();
+        let actual = JAN_2024 + 2 * HOUR as i64;
+        assert!((estimate - actual).abs() < 1_000, "{}", estimate - actual);
+    }
+
+    #[test]
+    fn short_intervals_do_not_calibrate() {
+        let clock = RtcClock::new(JAN_2024, 0);
+        let clock = clock.resync(JAN_2024 + 600_000_000, 599_000_000);
+        assert_eq!(clock.drift_ppb(), None);
+        assert_eq!(clock.now(599_000_000), Some(JAN_2024 + 600_000_000));
+    }
+
+    #[test]
+    fn implausible_drift_is_ignored() {
+        let clock = RtcClock::from_parts(JAN_2024, 0, Some(50_000));
+        // Ten minutes off after an hour: a bad sync, not a 17% drift.
+        let clock = clock.resync(JAN_2024 + (HOUR + 600 * SECOND) as i64, HOUR);
+        assert_eq!(clock.drift_ppb(), Some(50_000));
+    }
+
+    #[test]
+    fn resyncs_on_interval_or_error_bound() {
+        // Uncalibrated: 500 ppm reaches 30 s after 60 000 s (16.7 h), so the
+        // 12 hour interval comes first.
+        let clock = RtcClock::new(JAN_2024, 0);
+        assert!(!clock.needs_sync(12 * HOUR - 1, &POLICY));
+        assert!(clock.needs_sync(12 * HOUR, &POLICY));
+        assert_eq!(clock.error_bound_micros(HOUR), Some(1_800_000));
+
+        let tight = SyncPolicy {
+            max_error_micros: SECOND,
+            ..POLICY
+        };
+        assert!(!clock.needs_sync(1_999 * SECOND, &tight));
+        assert!(clock.needs_sync(2_000 * SECOND, &tight));
+
+        // Calibrated clocks are trusted ten times longer.
+        let calibrated = RtcClock::from_parts(JAN_2024, 0, Some(0));
+        assert!(!calibrated.needs_sync(19_999 * SECOND, &tight));
+        assert!(calibrated.needs_sync(20_000 * SECOND, &tight));
+    }
+}
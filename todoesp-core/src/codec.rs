@@ -0,0 +1,448 @@
+//! Compact binary encoding of tasks, for caching them across deep sleep.
+//!
+//! JSON is far too verbose to keep in the few kilobytes of memory that survive
+//! deep sleep, so tasks are re-encoded as a version byte, a task count and then
+//! each task's displayed fields: integers as LEB128 varints and strings
+//! length-prefixed. Only what [`Task::into_snapshot`] needs is kept; see
+//! [`Task`]'s encoding in `task.rs`.
+//!
+//! For storage that outlives a power cycle (i.e. flash), [`encode_record`]
+//! wraps the encoding in a [`TaskRecord`] with a magic number, the fetch time,
+//! the timezone, the number of tasks fetched and a CRC-32, so that erased, torn
+//! or stale data is detected on reading.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::task::Task;
+use crate::time::PosixTz;
+
+/// Version of the encoding, bumped whenever the layout of the tasks or the
+/// record changes so that data written by older firmware is rejected rather
+/// than misread.
+pub const FORMAT_VERSION: u8 = 3;
+/// Bytes before the first task: the version and a little-endian `u16` count.
+const HEADER_LEN: usize = 3;
+/// Marks the start of a [`TaskRecord`].
+const RECORD_MAGIC: [u8; 4] = *b"TDSK";
+/// Bytes before a record's body: the magic, the version, and the body's
+/// length and CRC-32 as little-endian `u32`s.
+const RECORD_HEADER_LEN: usize = 13;
+
+/// Why encoded tasks could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data ends in the middle of a value.
+    Truncated,
+    /// The data was written in another format version.
+    Version(u8),
+    /// A value is out of range, or a string is not UTF-8.
+    Invalid,
+    /// There is no record here, e.g. because the flash is erased.
+    Missing,
+    /// The record's checksum doesn't match its contents.
+    Checksum,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "truncated task data"),
+            Self::Version(version) => write!(f, "unsupported task data version {version}"),
+            Self::Invalid => write!(f, "invalid task data"),
+            Self::Missing => write!(f, "no task data"),
+            Self::Checksum => write!(f, "task data checksum mismatch"),
+        }
+    }
+}
+
+/// Encode as many of `tasks` as fit into `buf`, in order, returning the number
+/// of bytes written.
+///
+/// Tasks that don't fit are dropped whole, so callers should pass the tasks in
+/// display order. Returns 0 only if `buf` can't even hold the header.
+pub fn encode_tasks(tasks: &[Task], buf: &mut [u8]) -> usize {
+    if buf.len() < HEADER_LEN {
+        return 0;
+    }
+    let mut writer = Writer::new(buf);
+    writer.len = HEADER_LEN;
+    let mut count: u16 = 0;
+    for task in tasks.iter().take(u16::MAX as usize) {
+        let start = writer.len;
+        if task.encode(&mut writer).is_none() {
+            writer.len = start;
+            break;
+        }
+        count += 1;
+    }
+
+    let len = writer.len;
+    buf[0] = FORMAT_VERSION;
+    buf[1..HEADER_LEN].copy_from_slice(&count.to_le_bytes());
+    len
+}
+
+/// Decode tasks written by [`encode_tasks`].
+pub fn decode_tasks(bytes: &[u8]) -> Result<Vec<Task>, DecodeError> {
+    let mut reader = Reader::new(bytes);
+    let version = reader.u8()?;
+    if version != FORMAT_VERSION {
+        return Err(DecodeError::Version(version));
+    }
+    let count = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+    (0..count).map(|_| Task::decode(&mut reader)).collect()
+}
+
+/// A task list together with when and where it was fetched.
+#[derive(Debug)]
+pub struct TaskRecord {
+    /// Unix time (s) at which the tasks were fetched.
+    pub fetched_unix: i64,
+    /// The timezone the tasks were shown in, if it came from the Todoist
+    /// account rather than the firmware's configuration. The resolved rule is
+    /// kept rather than the zone's name, which may not resolve again.
+    pub timezone: Option<PosixTz>,
+    /// How many tasks were fetched. `tasks` may hold only the first of them,
+    /// as [`encode_record`] keeps only as many as fit.
+    pub total: usize,
+    pub tasks: Vec<Task>,
+}
+
+/// Encode `record` as a checksummed record, returning the number of bytes
+/// written, or 0 if `buf` is too small for even an empty list.
+///
+/// As many tasks are kept as fit; see [`encode_tasks`].
+pub fn encode_record(record: &TaskRecord, buf: &mut [u8]) -> usize {
+    let Some(body) = buf.get_mut(RECORD_HEADER_LEN..) else {
+        return 0;
+    };
+    let mut writer = Writer::new(body);
+    let fields = writer
+        .signed(record.fetched_unix)
+        .and_then(|()| {
+            let rule = record.timezone.as_ref().map(ToString::to_string);
+            writer.str(rule.as_deref().unwrap_or_default())
+        })
+        .and_then(|()| writer.varint(record.total as u64));
+    if fields.is_none() {
+        return 0;
+    }
+    let tasks_start = writer.len;
+    let tasks_len = encode_tasks(&record.tasks, &mut body[tasks_start..]);
+    if tasks_len == 0 {
+        return 0;
+    }
+    let end = RECORD_HEADER_LEN + tasks_start + tasks_len;
+
+    let body = &buf[RECORD_HEADER_LEN..end];
+    let (len, crc) = ((body.len() as u32).to_le_bytes(), crc32(body).to_le_bytes());
+    buf[..4].copy_from_slice(&RECORD_MAGIC);
+    buf[4] = FORMAT_VERSION;
+    buf[5..9].copy_from_slice(&len);
+    buf[9..RECORD_HEADER_LEN].copy_from_slice(&crc);
+    end
+}
+
+/// Decode a record written by [`encode_record`]. Anything after the record
+/// in `bytes` (e.g. the rest of a flash partition) is ignored.
+pub fn decode_record(bytes: &[u8]) -> Result<TaskRecord, DecodeError> {
+    let header = bytes.get(..RECORD_HEADER_LEN).ok_or(DecodeError::Missing)?;
+    if header[..4] != RECORD_MAGIC {
+        return Err(DecodeError::Missing);
+    }
+    if header[4] != FORMAT_VERSION {
+        return Err(DecodeError::Version(header[4]));
+    }
+    let word = |at: usize| {
+        u32::from_le_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]])
+    };
+    let len = usize::try_from(word(5)).map_err(|_| DecodeError::Invalid)?;
+    let body = RECORD_HEADER_LEN
+        .checked_add(len)
+        .and_then(|end| bytes.get(RECORD_HEADER_LEN..end))
+        .ok_or(DecodeError::Truncated)?;
+    if crc32(body) != word(9) {
+        return Err(DecodeError::Checksum);
+    }
+
+    let mut reader = Reader::new(body);
+    let fetched_unix = reader.signed()?;
+    let rule = reader.str()?;
+    let timezone = match rule.as_str() {
+        "" => None,
+        rule => Some(PosixTz::parse(rule).ok_or(DecodeError::Invalid)?),
+    };
+    let total = reader.int()?;
+    Ok(TaskRecord {
+        fetched_unix,
+        timezone,
+        total,
+        tasks: decode_tasks(&body[reader.pos..])?,
+    })
+}
+
+/// The CRC-32 (IEEE 802.3, as used by zlib) of `bytes`, computed bitwise to
+/// avoid a 1 KiB table; records are only checked once per wake.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Appends values to a fixed buffer; every method returns `None` once the
+/// buffer is full.
+pub(crate) struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) -> Option<()> {
+        *self.buf.get_mut(self.len)? = value;
+        self.len += 1;
+        Some(())
+    }
+
+    pub(crate) fn varint(&mut self, mut value: u64) -> Option<()> {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                return self.u8(byte);
+            }
+            self.u8(byte | 0x80)?;
+        }
+    }
+
+    /// A signed integer, zigzag-encoded so small negatives stay small.
+    pub(crate) fn signed(&mut self, value: i64) -> Option<()> {
+        self.varint(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    pub(crate) fn str(&mut self, value: &str) -> Option<()> {
+        self.varint(value.len() as u64)?;
+        let end = self.len.checked_add(value.len())?;
+        self.buf
+            .get_mut(self.len..end)?
+            .copy_from_slice(value.as_bytes());
+        self.len = end;
+        Some(())
+    }
+}
+
+/// Reads values written by a [`Writer`].
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, DecodeError> {
+        let byte = *self.bytes.get(self.pos).ok_or(DecodeError::Truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Invalid)
+    }
+
+    pub(crate) fn signed(&mut self) -> Result<i64, DecodeError> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    /// A varint that must fit in `T`.
+    pub(crate) fn int<T: TryFrom<u64>>(&mut self) -> Result<T, DecodeError> {
+        T::try_from(self.varint()?).map_err(|_| DecodeError::Invalid)
+    }
+
+    pub(crate) fn str(&mut self) -> Result<String, DecodeError> {
+        let len = usize::try_from(self.varint()?).map_err(|_| DecodeError::Invalid)?;
+        let end = self.pos.checked_add(len).ok_or(DecodeError::Invalid)?;
+        let bytes = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(DecodeError::Truncated)?;
+        self.pos = end;
+        core::str::from_utf8(bytes)
+            .map(String::from)
+            .map_err(|_| DecodeError::Invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::parse_tasks;
+
+    #[test]
+    fn varints_round_trip() {
+        let mut buf = [0u8; 64];
+        let mut writer = Writer::new(&mut buf);
+        for value in [0, 1, 127, 128, 300, u64::MAX] {
+            writer.varint(value).unwrap();
+        }
+        for value in [0, -1, 1, -64, 64, i64::MIN, i64::MAX] {
+            writer.signed(value).unwrap();
+        }
+        writer.str("héllo").unwrap();
+        // 127 fits in one byte, 128 needs two.
+        assert_eq!(&buf[2..5], &[0x7f, 0x80, 0x01]);
+
+        let mut reader = Reader::new(&buf);
+        for value in [0, 1, 127, 128, 300, u64::MAX] {
+            assert_eq!(reader.varint(), Ok(value));
+        }
+        for value in [0, -1, 1, -64, 64, i64::MIN, i64::MAX] {
+            assert_eq!(reader.signed(), Ok(value));
+        }
+        assert_eq!(reader.str().as_deref(), Ok("héllo"));
+    }
+
+    #[test]
+    fn drops_tasks_that_do_not_fit() {
+        let json = br#"{"results":[
+            {"id":"1","content":"First"},
+            {"id":"2","content":"Second"},
+            {"id":"3","content":"Third"}
+        ]}"#;
+        let tasks = parse_tasks(json).expect("valid json");
+        let mut buf = [0u8; 256];
+        let full = encode_tasks(&tasks, &mut buf);
+        assert_eq!(decode_tasks(&buf[..full]).unwrap().len(), 3);
+
+        // Room for the header and two tasks, but not the third.
+        let mut small = [0u8; 256];
+        let len = encode_tasks(&tasks, &mut small[..full - 1]);
+        let decoded = decode_tasks(&small[..len]).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(encode_tasks(&tasks, &mut small[..2]), 0);
+    }
+
+    #[test]
+    fn rejects_other_versions_and_damaged_data() {
+        let tasks = parse_tasks(br#"{"results":[{"id":"1","content":"Only"}]}"#).unwrap();
+        let mut buf = [0u8; 64];
+        let len = encode_tasks(&tasks, &mut buf);
+
+        assert_eq!(
+            decode_tasks(&buf[..len - 1]).unwrap_err(),
+            DecodeError::Truncated
+        );
+        let mut old = buf;
+        old[0] = FORMAT_VERSION + 1;
+        assert_eq!(
+            decode_tasks(&old[..len]).unwrap_err(),
+            DecodeError::Version(FORMAT_VERSION + 1)
+        );
+        assert_eq!(decode_tasks(&[]).unwrap_err(), DecodeError::Truncated);
+    }
+
+    #[test]
+    fn crc32_matches_the_standard_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    fn record(timezone: Option<&str>) -> TaskRecord {
+        TaskRecord {
+            fetched_unix: 1_704_067_200,
+            timezone: timezone.and_then(PosixTz::parse),
+            total: 1,
+            tasks: parse_tasks(br#"{"results":[{"id":"1","content":"Only"}]}"#).unwrap(),
+        }
+    }
+
+    #[test]
+    fn records_round_trip_within_a_larger_region() {
+        for timezone in [None, Some("GMT0IST,M3.5.0/1,M10.5.0")] {
+            let original = record(timezone);
+            // Flash reads back as 0xff past the end of the record.
+            let mut flash = [0xffu8; 128];
+            let len = encode_record(&original, &mut flash);
+            assert!(len > RECORD_HEADER_LEN);
+
+            let decoded = decode_record(&flash).unwrap();
+            assert_eq!(decoded.fetched_unix, original.fetched_unix);
+            assert_eq!(decoded.timezone, original.timezone);
+            assert_eq!(decoded.total, 1);
+            assert_eq!(decoded.tasks, original.tasks);
+
+            // Without room for the task, the record is kept but empty.
+            let short = encode_record(&original, &mut flash[..len - 1]);
+            assert!(decode_record(&flash[..short]).unwrap().tasks.is_empty());
+        }
+    }
+
+    #[test]
+    fn records_cut_short_keep_the_total() {
+        let json = br#"{"results":[
+            {"id":"1","content":"First"},
+            {"id":"2","content":"Second"},
+            {"id":"3","content":"Third"}
+        ]}"#;
+        let tasks = parse_tasks(json).unwrap();
+        let original = TaskRecord {
+            fetched_unix: 1_704_067_200,
+            timezone: None,
+            total: 250,
+            tasks,
+        };
+        let mut buf = [0u8; 256];
+        let full = encode_record(&original, &mut buf);
+        let decoded = decode_record(&buf[..full]).unwrap();
+        assert_eq!((decoded.tasks.len(), decoded.total), (3, 250));
+
+        let short = encode_record(&original, &mut buf[..full - 1]);
+        let decoded = decode_record(&buf[..short]).unwrap();
+        assert_eq!((decoded.tasks.len(), decoded.total), (2, 250));
+    }
+
+    #[test]
+    fn records_detect_erasure_corruption_and_old_versions() {
+        let mut buf = [0u8; 64];
+        let len = encode_record(&record(None), &mut buf);
+
+        assert_eq!(
+            decode_record(&[0xff; 64]).unwrap_err(),
+            DecodeError::Missing
+        );
+        assert_eq!(
+            decode_record(&buf[..len - 1]).unwrap_err(),
+            DecodeError::Truncated
+        );
+        let mut corrupt = buf;
+        corrupt[len - 1] ^= 0x20;
+        assert_eq!(decode_record(&corrupt).unwrap_err(), DecodeError::Checksum);
+        let mut old = buf;
+        old[4] = FORMAT_VERSION - 1;
+        assert_eq!(
+            decode_record(&old).unwrap_err(),
+            DecodeError::Version(FORMAT_VERSION - 1)
+        );
+    }
+}
@@ -8,8 +8,9 @@
 
 use core::hash::Hasher;
 
-use chrono::{Datelike, NaiveDate};
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike};
 
+use crate::markdown::RichText;
 use crate::snapshot::TaskSnapshot;
 
 /// A small deterministic FNV-1a hasher.
@@ -42,13 +43,11 @@ impl Hasher for Fnv1a {
 /// [`OctColor`](epd_waveshare::color::OctColor) is not `Hash`.
 fn hash_snapshot(hasher: &mut impl Hasher, task: &TaskSnapshot) {
     hasher.write_u8(task.marker_color.get_nibble());
-    hasher.write(task.title.as_bytes());
-    hasher.write_u8(0);
+    hash_rich_text(hasher, &task.title);
     match &task.description {
         Some(text) => {
             hasher.write_u8(1);
-            hasher.write(text.as_bytes());
-            hasher.write_u8(0);
+            hash_rich_text(hasher, text);
         }
         None => hasher.write_u8(2),
     }
@@ -62,21 +61,68 @@ fn hash_snapshot(hasher: &mut impl Hasher, task: &TaskSnapshot) {
         }
         None => hasher.write_u8(2),
     }
+    hasher.write_u8(u8::from(task.recurring));
+    match &task.deadline {
+        Some(text) => {
+            hasher.write_u8(1);
+            hasher.write(text.as_bytes());
+            hasher.write_u8(0);
+        }
+        None => hasher.write_u8(2),
+    }
+    hasher.write_u8(task.deadline_color.get_nibble());
+}
+
+/// Feed styled text into `hasher`, so restyling a run also changes the hash.
+fn hash_rich_text(hasher: &mut impl Hasher, text: &RichText) {
+    hasher.write_usize(text.spans().len());
+    for span in text.spans() {
+        hasher.write_u8(span.style.bits());
+        hasher.write(span.text.as_bytes());
+        hasher.write_u8(0);
+    }
 }
 
 /// Fingerprint the task screen: the date plus every task's rendered fields.
+/// `tasks` may be just the start of a list of `total` tasks, as the rest are
+/// only counted on screen.
 ///
 /// Two task screens with the same fingerprint look identical, so the firmware
 /// can leave the panel untouched when this value is unchanged.
-pub fn fingerprint_tasks(date: NaiveDate, tasks: &[TaskSnapshot]) -> u64 {
+pub fn fingerprint_tasks(date: NaiveDate, tasks: &[TaskSnapshot], total: usize) -> u64 {
     let mut hasher = Fnv1a::default();
     hasher.write_u8(b'T');
+    hash_screen(&mut hasher, date, tasks, total);
+    hasher.finish()
+}
+
+/// Fingerprint the offline screen: the cached task list, headed by the time
+/// the device went offline (`since`) instead of the last update.
+///
+/// Always distinct from the [`fingerprint_tasks`] value for the same tasks, so
+/// losing or regaining the connection always redraws the header.
+pub fn fingerprint_offline(
+    date: NaiveDate,
+    since: NaiveTime,
+    tasks: &[TaskSnapshot],
+    total: usize,
+) -> u64 {
+    let mut hasher = Fnv1a::default();
+    hasher.write_u8(b'O');
+    hasher.write_u32(since.num_seconds_from_midnight());
+    hash_screen(&mut hasher, date, tasks, total);
+    hasher.finish()
+}
+
+/// Feed the date, the task count and every task of a task screen into
+/// `hasher`.
+fn hash_screen(hasher: &mut impl Hasher, date: NaiveDate, tasks: &[TaskSnapshot], total: usize) {
     hasher.write_i32(date.num_days_from_ce());
+    hasher.write_usize(total.max(tasks.len()));
     hasher.write_usize(tasks.len());
     for task in tasks {
-        hash_snapshot(&mut hasher, task);
+        hash_snapshot(hasher, task);
     }
-    hasher.finish()
 }
 
 /// Fingerprint a status screen identified by `code`.
@@ -100,11 +146,14 @@ mod tests {
     fn snapshot(title: &str) -> TaskSnapshot {
         TaskSnapshot {
             marker_color: Colour::Black,
-            title: title.to_string(),
+            title: RichText::plain(title),
             description: None,
             when: "today".to_string(),
             when_color: Colour::Black,
             duration: None,
+            recurring: false,
+            deadline: None,
+            deadline_color: Colour::Black,
         }
     }
 
@@ -116,8 +165,8 @@ mod tests {
     fn identical_content_has_the_same_fingerprint() {
         let day = date(2021, 1, 1);
         assert_eq!(
-            fingerprint_tasks(day, &[snapshot("a"), snapshot("b")]),
-            fingerprint_tasks(day, &[snapshot("a"), snapshot("b")]),
+            fingerprint_tasks(day, &[snapshot("a"), snapshot("b")], 0),
+            fingerprint_tasks(day, &[snapshot("a"), snapshot("b")], 0),
         );
     }
 
@@ -125,8 +174,8 @@ mod tests {
     fn changing_a_task_changes_the_fingerprint() {
         let day = date(2021, 1, 1);
         assert_ne!(
-            fingerprint_tasks(day, &[snapshot("a")]),
-            fingerprint_tasks(day, &[snapshot("b")]),
+            fingerprint_tasks(day, &[snapshot("a")], 0),
+            fingerprint_tasks(day, &[snapshot("b")], 0),
         );
     }
 
@@ -134,16 +183,57 @@ mod tests {
     fn reordering_tasks_changes_the_fingerprint() {
         let day = date(2021, 1, 1);
         assert_ne!(
-            fingerprint_tasks(day, &[snapshot("a"), snapshot("b")]),
-            fingerprint_tasks(day, &[snapshot("b"), snapshot("a")]),
+            fingerprint_tasks(day, &[snapshot("a"), snapshot("b")], 0),
+            fingerprint_tasks(day, &[snapshot("b"), snapshot("a")], 0),
         );
     }
 
     #[test]
     fn changing_the_date_changes_the_fingerprint() {
         assert_ne!(
-            fingerprint_tasks(date(2021, 1, 1), &[snapshot("a")]),
-            fingerprint_tasks(date(2021, 1, 2), &[snapshot("a")]),
+            fingerprint_tasks(date(2021, 1, 1), &[snapshot("a")], 0),
+            fingerprint_tasks(date(2021, 1, 2), &[snapshot("a")], 0),
+        );
+    }
+
+    #[test]
+    fn changing_recurrence_changes_the_fingerprint() {
+        let day = date(2021, 1, 1);
+        let recurring = TaskSnapshot {
+            recurring: true,
+            ..snapshot("a")
+        };
+        assert_ne!(
+            fingerprint_tasks(day, &[snapshot("a")], 0),
+            fingerprint_tasks(day, &[recurring], 0),
+        );
+    }
+
+    #[test]
+    fn tasks_past_the_shown_ones_count() {
+        let day = date(2021, 1, 1);
+        assert_eq!(
+            fingerprint_tasks(day, &[snapshot("a")], 0),
+            fingerprint_tasks(day, &[snapshot("a")], 1),
+        );
+        assert_ne!(
+            fingerprint_tasks(day, &[snapshot("a")], 1),
+            fingerprint_tasks(day, &[snapshot("a")], 2),
+        );
+    }
+
+    #[test]
+    fn offline_fingerprints_track_the_offline_time() {
+        let day = date(2021, 1, 1);
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let ten = NaiveTime::from_hms_opt(10, 0, 0).unwrap();
+        assert_ne!(
+            fingerprint_offline(day, nine, &[snapshot("a")], 0),
+            fingerprint_tasks(day, &[snapshot("a")], 0),
+        );
+        assert_ne!(
+            fingerprint_offline(day, nine, &[snapshot("a")], 0),
+            fingerprint_offline(day, ten, &[snapshot("a")], 0),
         );
     }
 
@@ -152,7 +242,7 @@ mod tests {
         assert_ne!(fingerprint_status(0), fingerprint_status(1));
         assert_ne!(
             fingerprint_status(0),
-            fingerprint_tasks(date(2021, 1, 1), &[]),
+            fingerprint_tasks(date(2021, 1, 1), &[], 0),
         );
     }
 }
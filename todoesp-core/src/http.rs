@@ -0,0 +1,266 @@
+//! HTTP helpers that don't need a network stack.
+//!
+//! This includes deciding what a failed Todoist request means for the device
+//! ([`FetchFailure`]), so that the firmware's reaction to each status code can
+//! be tested on the host.
+
+use chrono::NaiveDateTime;
+
+/// Parse an HTTP `Date` header value into a Unix timestamp.
+///
+/// RFC 7231 section 7.1.1.1 requires recipients to accept three formats:
+/// the preferred IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`) and the
+/// obsolete RFC 850 (`Sunday, 06-Nov-94 08:49:37 GMT`) and asctime
+/// (`Sun Nov  6 08:49:37 1994`) forms. All three are in UTC.
+pub fn parse_http_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+    const FORMATS: [&str; 3] = [
+        "%a, %d %b %Y %H:%M:%S GMT",
+        "%A, %d-%b-%y %H:%M:%S GMT",
+        "%a %b %e %H:%M:%S %Y",
+    ];
+    FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|dt| dt.and_utc().timestamp())
+}
+
+/// The longest `Retry-After` delay honoured, in seconds. A buggy or hostile
+/// server must not be able to put the device to sleep for years.
+pub const MAX_RETRY_AFTER_SECS: u64 = 2 * 60 * 60;
+
+/// Parse a `Retry-After` header value into a delay in seconds.
+///
+/// The header holds either a number of seconds or an HTTP date (see
+/// [`parse_http_date`]); a date is measured from `now_unix`, and one already
+/// in the past means "retry now". Without a `now_unix` to measure from, a date
+/// is ignored.
+pub fn parse_retry_after(value: &str, now_unix: Option<i64>) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(seconds);
+    }
+    let now_unix = now_unix?;
+    parse_http_date(value).map(|date| date.saturating_sub(now_unix).max(0) as u64)
+}
+
+/// Why a Todoist request failed, and so how the device should react.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchFailure {
+    /// No usable response arrived: DNS, TCP or TLS failed, or the body was
+    /// cut short.
+    Network,
+    /// HTTP 401 or 403: the API token is wrong or has been revoked. Retrying
+    /// can't help until the configuration is fixed.
+    Unauthorized,
+    /// HTTP 429: too many requests. Holds the `Retry-After` delay in seconds,
+    /// if the server sent one.
+    RateLimited(Option<u64>),
+    /// HTTP 5xx: Todoist is having trouble.
+    Server(u16),
+    /// Any other unexpected status, such as 400 for an invalid filter.
+    Rejected(u16),
+    /// The response was not the JSON we expected.
+    Parse,
+}
+
+impl FetchFailure {
+    /// Classify an HTTP status, or return `None` if it is a success.
+    ///
+    /// `retry_after` is the response's `Retry-After` delay in seconds (see
+    /// [`parse_retry_after`]); it is only meaningful for a 429.
+    pub fn from_status(status: u16, retry_after: Option<u64>) -> Option<Self> {
+        Some(match status {
+            200..=299 => return None,
+            401 | 403 => Self::Unauthorized,
+            429 => Self::RateLimited(retry_after),
+            500..=599 => Self::Server(status),
+            _ => Self::Rejected(status),
+        })
+    }
+
+    /// Whether the same request may succeed if it is retried after a short
+    /// backoff. A rate limit is not: the server says how long to wait, which
+    /// is usually longer than is worth staying awake for.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Network | Self::Server(_))
+    }
+
+    /// Whether the failure is probably temporary, so the last good task list
+    /// is still worth showing. The others need the user's attention.
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::Network | Self::RateLimited(_) | Self::Server(_))
+    }
+
+    /// How long the server asked us to wait before trying again, in seconds,
+    /// at most [`MAX_RETRY_AFTER_SECS`].
+    pub fn retry_after_secs(self) -> Option<u64> {
+        match self {
+            Self::RateLimited(delay) => delay.map(|secs| secs.min(MAX_RETRY_AFTER_SECS)),
+            _ => None,
+        }
+    }
+
+    /// A code identifying the failure's status screen, for
+    /// [`fingerprint_status`](crate::fingerprint_status). Codes 0 and 1 are
+    /// left for the failures that happen before any request is made (WiFi and
+    /// the clock).
+    pub fn status_code(self) -> u8 {
+        match self {
+            Self::Network => 2,
+            Self::Unauthorized => 3,
+            Self::RateLimited(_) => 4,
+            Self::Server(_) => 5,
+            Self::Rejected(_) => 6,
+            Self::Parse => 7,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1994-11-06T08:49:37Z, the example used throughout RFC 7231.
+    const EXAMPLE: i64 = 784_111_777;
+
+    #[test]
+    fn parses_all_three_date_formats() {
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(EXAMPLE)
+        );
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(EXAMPLE)
+        );
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(EXAMPLE));
+    }
+
+    #[test]
+    fn parses_a_current_date() {
+        assert_eq!(
+            parse_http_date(" Mon, 01 Jan 2024 00:00:00 GMT\r\n"),
+            Some(1_704_067_200)
+        );
+    }
+
+    #[test]
+    fn rejects_other_zones_and_garbage() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 +0100"), None);
+        assert_eq!(parse_http_date("Sun, 31 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("yesterday"), None);
+        assert_eq!(parse_http_date(""), None);
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        let now = Some(EXAMPLE);
+        assert_eq!(parse_retry_after("120", now), Some(120));
+        assert_eq!(parse_retry_after(" 0 ", now), Some(0));
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:51:37 GMT", now),
+            Some(120)
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:48:37 GMT", now),
+            Some(0)
+        );
+        assert_eq!(parse_retry_after("-5", now), None);
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn ignores_retry_after_dates_without_a_clock() {
+        // Measured from 1970, this would be decades.
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:51:37 GMT", None),
+            None
+        );
+        assert_eq!(parse_retry_after("120", None), Some(120));
+    }
+
+    #[test]
+    fn caps_the_retry_after_delay() {
+        let hostile = parse_retry_after("99999999999", None);
+        assert_eq!(hostile, Some(99_999_999_999));
+        assert_eq!(
+            FetchFailure::RateLimited(hostile).retry_after_secs(),
+            Some(MAX_RETRY_AFTER_SECS)
+        );
+        assert_eq!(
+            FetchFailure::RateLimited(Some(MAX_RETRY_AFTER_SECS - 1)).retry_after_secs(),
+            Some(MAX_RETRY_AFTER_SECS - 1)
+        );
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        assert_eq!(FetchFailure::from_status(200, None), None);
+        assert_eq!(FetchFailure::from_status(204, None), None);
+        assert_eq!(
+            FetchFailure::from_status(401, None),
+            Some(FetchFailure::Unauthorized)
+        );
+        assert_eq!(
+            FetchFailure::from_status(403, None),
+            Some(FetchFailure::Unauthorized)
+        );
+        assert_eq!(
+            FetchFailure::from_status(429, Some(30)),
+            Some(FetchFailure::RateLimited(Some(30)))
+        );
+        assert_eq!(
+            FetchFailure::from_status(503, Some(30)),
+            Some(FetchFailure::Server(503))
+        );
+        assert_eq!(
+            FetchFailure::from_status(400, None),
+            Some(FetchFailure::Rejected(400))
+        );
+        assert_eq!(
+            FetchFailure::from_status(302, None),
+            Some(FetchFailure::Rejected(302))
+        );
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        assert!(FetchFailure::Network.is_retryable());
+        assert!(FetchFailure::Server(502).is_retryable());
+        assert!(!FetchFailure::RateLimited(None).is_retryable());
+        assert!(!FetchFailure::Unauthorized.is_retryable());
+        assert!(!FetchFailure::Parse.is_retryable());
+
+        assert!(FetchFailure::RateLimited(Some(60)).is_transient());
+        assert!(!FetchFailure::Rejected(404).is_transient());
+        assert_eq!(
+            FetchFailure::RateLimited(Some(60)).retry_after_secs(),
+            Some(60)
+        );
+        assert_eq!(FetchFailure::Server(500).retry_after_secs(), None);
+    }
+
+    #[test]
+    fn status_codes_are_distinct() {
+        let failures = [
+            FetchFailure::Network,
+            FetchFailure::Unauthorized,
+            FetchFailure::RateLimited(None),
+            FetchFailure::Server(500),
+            FetchFailure::Rejected(400),
+            FetchFailure::Parse,
+        ];
+        for (i, a) in failures.iter().enumerate() {
+            assert!(a.status_code() >= 2, "{a:?}");
+            for b in &failures[i + 1..] {
+                assert_ne!(a.status_code(), b.status_code(), "{a:?} and {b:?}");
+            }
+        }
+        // The code depends only on the kind of failure, not its details.
+        assert_eq!(
+            FetchFailure::Server(500).status_code(),
+            FetchFailure::Server(503).status_code()
+        );
+    }
+}
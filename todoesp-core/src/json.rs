@@ -0,0 +1,1225 @@
+//! Streaming JSON parsing for Todoist API responses.
+//!
+//! [`StreamArrayParser`] picks the elements of one array out of a response as
+//! it streams off the network, and [`FromJson`] turns each element directly
+//! into a model type using [`hifijson`]'s tokeniser. Only a single element is
+//! ever materialised at a time, keeping peak memory small enough for the
+//! device's tiny heap.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use hifijson::num::{Lex as _, LexWrite as _};
+use hifijson::str::{Lex as _, LexAlloc as _};
+use hifijson::token::Lex;
+use hifijson::{Expect, SliceLexer};
+
+/// The longest field path (e.g. `due.date`) retained by a [`ParseError`];
+/// longer paths are truncated.
+const FIELD_PATH_LEN: usize = 32;
+
+/// Why a JSON value could not be turned into one of the Todoist model types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The input is not well-formed JSON (a bad token, an unterminated
+    /// string, a missing `:`, ...).
+    MalformedJson,
+    /// The value is valid JSON but of a type the field does not accept (e.g. a
+    /// string where a number was expected).
+    TypeMismatch,
+    /// A number does not fit the field's integer type.
+    NumberOutOfRange,
+    /// A required field is absent from its object.
+    MissingField(&'static str),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedJson => f.write_str("malformed JSON"),
+            Self::TypeMismatch => f.write_str("unexpected value type"),
+            Self::NumberOutOfRange => f.write_str("number out of range"),
+            Self::MissingField(name) => write!(f, "missing field `{name}`"),
+        }
+    }
+}
+
+/// An error encountered while parsing a Todoist API response.
+///
+/// Besides the [`ParseErrorKind`], the error records where things went wrong
+/// so a schema change on Todoist's side can be diagnosed from the serial
+/// console (or the status screen) alone: the dotted path of the field being
+/// decoded, the index of the element within the streamed array and the
+/// absolute byte offset within the response stream. Everything is held
+/// inline, so creating and logging an error never allocates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    field: heapless::String<FIELD_PATH_LEN>,
+    index: Option<usize>,
+    offset: Option<usize>,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind) -> Self {
+        Self {
+            kind,
+            field: heapless::String::new(),
+            index: None,
+            offset: None,
+        }
+    }
+
+    /// What went wrong.
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// The dotted path of the field being decoded (e.g. `due.date`), if the
+    /// error occurred inside an object member.
+    pub fn field(&self) -> Option<&str> {
+        Some(self.field.as_str()).filter(|field| !field.is_empty())
+    }
+
+    /// The zero-based index of the failing element within the streamed array
+    /// (e.g. the response's `results`).
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// The absolute byte offset within the response at which parsing stopped.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// Record that the error occurred while decoding the member `key`,
+    /// prefixing it to any (more deeply nested) field already recorded.
+    pub(crate) fn within(mut self, key: &str) -> Self {
+        let inner = core::mem::take(&mut self.field);
+        let parts = [key, ".", inner.as_str()];
+        let parts = if inner.is_empty() {
+            &parts[..1]
+        } else {
+            &parts[..]
+        };
+        for c in parts.iter().flat_map(|part| part.chars()) {
+            if self.field.push(c).is_err() {
+                break;
+            }
+        }
+        self
+    }
+
+    /// Locate the error within the stream, given the absolute offset at which
+    /// the failing value started and the index of the enclosing object. Any
+    /// offset already recorded is taken as relative to that value.
+    pub(crate) fn located(mut self, start: usize, index: Option<usize>) -> Self {
+        self.offset = Some(start + self.offset.unwrap_or_default());
+        self.index = self.index.or(index);
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(field) = self.field() {
+            write!(f, " in `{field}`")?;
+        }
+        if let Some(index) = self.index {
+            write!(f, " of element #{index}")?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {offset}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Expect> for ParseError {
+    fn from(_: Expect) -> Self {
+        ParseError::new(ParseErrorKind::MalformedJson)
+    }
+}
+
+/// The key path of the item array in Todoist's paginated list responses
+/// (tasks, projects, labels, sections, collaborators, ...).
+pub const RESULTS_PATH: &[&str] = &["results"];
+
+/// The top-level key holding the opaque cursor for the next page of results.
+const CURSOR_KEY: &[u8] = b"next_cursor";
+/// The longest object key compared against the key path; longer keys can
+/// never match and are not retained.
+const KEY_LEN: usize = 32;
+/// The deepest container nesting tracked around the target array.
+const MAX_ENVELOPE_DEPTH: u32 = u32::BITS;
+
+/// Incrementally extracts the elements of one JSON array from a response.
+///
+/// The array is located by a key path: `["results"]` (the default, used by
+/// every Todoist list endpoint) finds the value of the top-level `results`
+/// key, `["sync", "items"]` would find `{"sync":{"items":[..]}}`, and an
+/// empty path expects the response itself to be a bare array. Rather than
+/// buffer the entire (potentially large) body, callers feed the response bytes
+/// in arbitrarily sized chunks via [`feed`](Self::feed). Each complete element
+/// of the array is parsed with [`FromJson`] and pushed to the output vector,
+/// so only a single element is ever buffered at a time.
+///
+/// Everything else in the response (the "envelope") is scanned structurally
+/// but not decoded, except for a top-level `next_cursor` value: once the
+/// stream is exhausted, [`finish`](Self::finish) checks that it wasn't cut
+/// short and [`next_cursor`](Self::next_cursor) tells the caller whether
+/// another page should be requested.
+pub struct StreamArrayParser<T> {
+    path: &'static [&'static str],
+    phase: Phase,
+    /// Number of envelope containers currently open.
+    depth: u32,
+    /// Bit `n` is set when envelope container `n` (zero-based) is an object.
+    objects: u32,
+    /// How many of the open containers lie on `path`. Containers are only
+    /// ever entered along the path from the root, so the innermost one is on
+    /// the path exactly when this equals `depth`.
+    on_path: u32,
+    /// Whether the next string in the envelope is an object member key.
+    expect_key: bool,
+    /// Whether the next non-whitespace byte in the envelope starts a value.
+    expect_value: bool,
+    /// What the value following the most recent member key is for.
+    role: Role,
+    /// The member key currently being read (bytes beyond [`KEY_LEN`] are
+    /// dropped and flagged in `key_overflow`).
+    key: heapless::Vec<u8, KEY_LEN>,
+    key_overflow: bool,
+    /// Whether the target array has already been found.
+    found: bool,
+    /// Bytes of the value currently being accumulated: an array element, or
+    /// the raw `next_cursor` value (reused between values).
+    value: Vec<u8>,
+    /// Container-nesting depth within the current element.
+    nesting: u32,
+    /// Whether the cursor is inside a JSON string literal.
+    in_string: bool,
+    /// Whether the previous byte was an unescaped backslash inside a string.
+    escaped: bool,
+    /// The decoded `next_cursor` value, if one has been seen and is not `null`.
+    next_cursor: Option<String>,
+    /// Absolute offset of the byte currently being fed.
+    position: usize,
+    /// Absolute offset at which the value in [`value`](Self::value) started.
+    start: usize,
+    /// Number of elements completed so far (the index of the next one).
+    index: usize,
+    _element: PhantomData<fn() -> T>,
+}
+
+enum Phase {
+    /// Scanning the envelope for the target array.
+    Envelope,
+    /// Inside an envelope string; `key` is set for object member keys.
+    EnvelopeString { key: bool },
+    /// Inside the target array, skipping whitespace/commas between elements.
+    BetweenElements,
+    /// Accumulating the bytes of one string, object or array element.
+    InElement,
+    /// Accumulating a bare number/literal element, which ends at the next
+    /// delimiter.
+    InScalar,
+    /// Accumulating a string `next_cursor` value.
+    CursorString,
+    /// Accumulating a bare (`null`) `next_cursor` value.
+    CursorLiteral,
+}
+
+/// What an envelope value is for, decided by the member key preceding it.
+#[derive(Clone, Copy)]
+enum Role {
+    /// Not of interest; skipped.
+    Skip,
+    /// An object on the key path that contains the target array.
+    Descend,
+    /// The target array itself.
+    Target,
+    /// The top-level `next_cursor` value.
+    Cursor,
+}
+
+impl<T> StreamArrayParser<T> {
+    /// A parser for the `results` array of a Todoist list response.
+    pub fn new() -> Self {
+        Self::with_path(RESULTS_PATH)
+    }
+
+    /// A parser for the array found by following the object keys in `path`
+    /// from the root of the response. An empty path parses a bare top-level
+    /// array.
+    pub fn with_path(path: &'static [&'static str]) -> Self {
+        Self {
+            path,
+            phase: Phase::Envelope,
+            depth: 0,
+            objects: 0,
+            on_path: 0,
+            expect_key: false,
+            expect_value: true,
+            role: Role::Skip,
+            key: heapless::Vec::new(),
+            key_overflow: false,
+            found: false,
+            value: Vec::new(),
+            nesting: 0,
+            in_string: false,
+            escaped: false,
+            next_cursor: None,
+            position: 0,
+            start: 0,
+            index: 0,
+            _element: PhantomData,
+        }
+    }
+
+    /// The cursor for the next page of results, or `None` if the response
+    /// reported (or has so far reported) no further pages.
+    ///
+    /// Only meaningful once the whole response has been fed and
+    /// [`finish`](Self::finish) has accepted it.
+    pub fn next_cursor(&self) -> Option<&str> {
+        self.next_cursor.as_deref()
+    }
+
+    /// Consume the parser, returning the cursor for the next page of results.
+    pub fn into_next_cursor(self) -> Option<String> {
+        self.next_cursor
+    }
+}
+
+impl<T> Default for StreamArrayParser<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: FromJson> StreamArrayParser<T> {
+    /// Feed a chunk of response bytes, appending any newly completed elements
+    /// to `out`. May be called repeatedly as data arrives off the network.
+    pub fn feed(&mut self, chunk: &[u8], out: &mut Vec<T>) -> Result<(), ParseError> {
+        for &byte in chunk {
+            self.feed_byte(byte, out)?;
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    /// Check, once the stream is exhausted, that the top-level value was
+    /// closed. A response cut short otherwise looks complete: Todoist sends
+    /// `next_cursor` after `results`, so a truncated page would silently end
+    /// the pagination.
+    pub fn finish(&self) -> Result<(), ParseError> {
+        if matches!(self.phase, Phase::Envelope) && self.depth == 0 && !self.expect_value {
+            Ok(())
+        } else {
+            Err(malformed().located(self.position, None))
+        }
+    }
+
+    fn feed_byte(&mut self, byte: u8, out: &mut Vec<T>) -> Result<(), ParseError> {
+        match self.phase {
+            Phase::Envelope => match byte {
+                b' ' | b'\t' | b'\r' | b'\n' => {}
+                b'"' if self.expect_key => {
+                    self.key.clear();
+                    self.key_overflow = false;
+                    self.escaped = false;
+                    self.phase = Phase::EnvelopeString { key: true };
+                }
+                b':' => self.expect_value = true,
+                b',' => {
+                    self.expect_key = self.in_object();
+                    self.expect_value = !self.expect_key;
+                }
+                b'}' | b']' => self.close_container(),
+                _ if self.expect_value => {
+                    self.expect_value = false;
+                    self.start_value(byte)?;
+                }
+                _ => {} // the remainder of a skipped number or literal
+            },
+            Phase::EnvelopeString { key } => {
+                if !self.escaped && byte == b'"' {
+                    self.phase = Phase::Envelope;
+                    if key {
+                        self.finish_key();
+                    }
+                    return Ok(());
+                }
+                self.escaped = !self.escaped && byte == b'\\';
+                if key && self.key.push(byte).is_err() {
+                    self.key_overflow = true;
+                }
+            }
+            Phase::BetweenElements => match byte {
+                b' ' | b'\t' | b'\r' | b'\n' | b',' => {}
+                b']' => self.phase = Phase::Envelope,
+                _ => {
+                    self.value.clear();
+                    self.value.push(byte);
+                    self.start = self.position;
+                    self.nesting = 0;
+                    self.in_string = false;
+                    self.escaped = false;
+                    self.phase = match byte {
+                        b'{' | b'[' => {
+                            self.nesting = 1;
+                            Phase::InElement
+                        }
+                        b'"' => {
+                            self.in_string = true;
+                            Phase::InElement
+                        }
+                        _ => Phase::InScalar,
+                    };
+                }
+            },
+            Phase::InElement => {
+                self.value.push(byte);
+                if self.in_string {
+                    if self.escaped {
+                        self.escaped = false;
+                    } else {
+                        match byte {
+                            b'\\' => self.escaped = true,
+                            b'"' => {
+                                self.in_string = false;
+                                if self.nesting == 0 {
+                                    self.finish_element(out)?;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                } else {
+                    match byte {
+                        b'"' => self.in_string = true,
+                        b'{' | b'[' => self.nesting += 1,
+                        b'}' | b']' => {
+                            self.nesting = self.nesting.saturating_sub(1);
+                            if self.nesting == 0 {
+                                self.finish_element(out)?;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Phase::InScalar => match byte {
+                b' ' | b'\t' | b'\r' | b'\n' | b',' | b']' => {
+                    self.finish_element(out)?;
+                    // The delimiter belongs to the array, so let it be seen.
+                    return self.feed_byte(byte, out);
+                }
+                _ => self.value.push(byte),
+            },
+            Phase::CursorString => {
+                self.value.push(byte);
+                if self.escaped {
+                    self.escaped = false;
+                } else {
+                    match byte {
+                        b'\\' => self.escaped = true,
+                        b'"' => self.finish_cursor()?,
+                        _ => {}
+                    }
+                }
+            }
+            Phase::CursorLiteral => {
+                if byte.is_ascii_alphanumeric() {
+                    self.value.push(byte);
+                } else {
+                    self.finish_cursor()?;
+                    // The delimiter (`,`, `}` or whitespace) belongs to the
+                    // enclosing object, so let the envelope see it.
+                    return self.feed_byte(byte, out);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the innermost open envelope container is an object.
+    fn in_object(&self) -> bool {
+        self.depth > 0 && self.objects & (1 << (self.depth - 1)) != 0
+    }
+
+    /// Decide what the value following the member key just read is for.
+    fn finish_key(&mut self) {
+        self.expect_key = false;
+        let key = &self.key[..];
+        let step = self.depth as usize;
+        let segment = step.checked_sub(1).and_then(|i| self.path.get(i));
+        self.role = if !self.key_overflow
+            && !self.found
+            && self.on_path == self.depth
+            && segment.map(|k| k.as_bytes()) == Some(key)
+        {
+            if step == self.path.len() {
+                Role::Target
+            } else {
+                Role::Descend
+            }
+        } else if self.depth == 1 && key == CURSOR_KEY {
+            Role::Cursor
+        } else {
+            Role::Skip
+        };
+    }
+
+    /// Handle the first byte of an envelope value.
+    fn start_value(&mut self, byte: u8) -> Result<(), ParseError> {
+        let role = if self.depth == 0 {
+            // The root value is the target itself when the path is empty, and
+            // otherwise the object the path starts from.
+            if self.path.is_empty() {
+                Role::Target
+            } else {
+                Role::Descend
+            }
+        } else {
+            core::mem::replace(&mut self.role, Role::Skip)
+        };
+
+        match (role, byte) {
+            (Role::Target, b'[') => {
+                self.found = true;
+                self.phase = Phase::BetweenElements;
+            }
+            (Role::Descend, b'{') => {
+                self.open_container(true)?;
+                self.on_path += 1;
+            }
+            (Role::Target | Role::Descend, _) => {
+                let error = ParseError::new(ParseErrorKind::TypeMismatch);
+                let error = match self.depth {
+                    0 => error,
+                    depth => error.within(self.path[depth as usize - 1]),
+                };
+                return Err(error.located(self.position, None));
+            }
+            (Role::Cursor, _) => {
+                self.value.clear();
+                self.value.push(byte);
+                self.start = self.position;
+                self.escaped = false;
+                self.phase = if byte == b'"' {
+                    Phase::CursorString
+                } else {
+                    Phase::CursorLiteral
+                };
+            }
+            (Role::Skip, b'{') => self.open_container(true)?,
+            (Role::Skip, b'[') => self.open_container(false)?,
+            (Role::Skip, b'"') => {
+                self.escaped = false;
+                self.phase = Phase::EnvelopeString { key: false };
+            }
+            (Role::Skip, _) => {} // a number or literal
+        }
+        Ok(())
+    }
+
+    /// Enter a skipped or on-path envelope container.
+    fn open_container(&mut self, object: bool) -> Result<(), ParseError> {
+        if self.depth == MAX_ENVELOPE_DEPTH {
+            return Err(malformed().located(self.position, None));
+        }
+        let bit = 1 << self.depth;
+        if object {
+            self.objects |= bit;
+        } else {
+            self.objects &= !bit;
+        }
+        self.depth += 1;
+        self.expect_key = object;
+        self.expect_value = !object;
+        Ok(())
+    }
+
+    /// Leave the innermost envelope container.
+    fn close_container(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        self.on_path = self.on_path.min(self.depth);
+        self.expect_key = false;
+        self.expect_value = false;
+    }
+
+    /// Decode the accumulated array element and push it to `out`.
+    fn finish_element(&mut self, out: &mut Vec<T>) -> Result<(), ParseError> {
+        let mut lexer = SliceLexer::new(&self.value);
+        let element = lexer
+            .exactly_one(Lex::ws_peek, T::from_json)
+            .map_err(|e: ParseError| {
+                let consumed = self.value.len() - lexer.as_slice().len();
+                e.located(self.start + consumed, Some(self.index))
+            })?;
+        out.push(element);
+        self.index += 1;
+        self.phase = Phase::BetweenElements;
+        Ok(())
+    }
+
+    /// Decode the accumulated `next_cursor` value and resume scanning.
+    fn finish_cursor(&mut self) -> Result<(), ParseError> {
+        let mut lexer = SliceLexer::new(&self.value);
+        self.next_cursor = lexer
+            .exactly_one(Lex::ws_peek, Option::<String>::from_json)
+            .map_err(|e: ParseError| {
+                let consumed = self.value.len() - lexer.as_slice().len();
+                e.within("next_cursor").located(self.start + consumed, None)
+            })?;
+        self.phase = Phase::Envelope;
+        Ok(())
+    }
+}
+
+// --- A tiny JSON deserialisation framework -----------------------------------
+//
+// hifijson does the tokenising; [`FromJson`] turns those tokens directly into
+// our types without an intermediate DOM and without serde. Implementations are
+// provided below for the primitive JSON types, `Option<T>`, arrays and the
+// bounded `heapless` collections, and the Todoist model types compose them.
+
+/// A type that can be parsed from a single JSON value.
+///
+/// `next` is the already-peeked first non-whitespace byte of the value (the
+/// `seq`/`exactly_one` combinators hand it over); the implementation must
+/// consume exactly that one value from `lexer`.
+pub trait FromJson: Sized {
+    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError>;
+}
+
+/// Parse a complete, buffered JSON document into `T`.
+///
+/// Suited to small responses such as the user profile; large arrays should be
+/// streamed through a [`StreamArrayParser`] instead.
+pub fn parse_json<T: FromJson>(json: &[u8]) -> Result<T, ParseError> {
+    let mut lexer = SliceLexer::new(json);
+    lexer
+        .exactly_one(Lex::ws_peek, T::from_json)
+        .map_err(|e: ParseError| e.located(json.len() - lexer.as_slice().len(), None))
+}
+
+impl FromJson for String {
+    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+        if next != b'"' {
+            return Err(ParseError::new(ParseErrorKind::TypeMismatch));
+        }
+        // `str_string` interprets escape sequences (borrowing from the input
+        // when it can), so a string allocates at most once — here.
+        let value = lexer.discarded().str_string().map_err(|_| malformed())?;
+        Ok(String::from(&*value))
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+        if !matches!(next, b't' | b'f') {
+            return Err(ParseError::new(ParseErrorKind::TypeMismatch));
+        }
+        match lexer.null_or_bool() {
+            Some(Some(value)) => Ok(value),
+            _ => Err(malformed()),
+        }
+    }
+}
+
+/// `null` parses to `None`; any other value is delegated to `T`.
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+        if next == b'n' {
+            return match lexer.null_or_bool() {
+                Some(None) => Ok(None),
+                _ => Err(malformed()),
+            };
+        }
+        T::from_json(next, lexer).map(Some)
+    }
+}
+
+/// Implement [`FromJson`] for integer types by lexing the number and parsing
+/// its textual form.
+macro_rules! impl_from_json_int {
+    ($($t:ty),* $(,)?) => {
+        $(impl FromJson for $t {
+            fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+                if !matches!(next, b'-' | b'0'..=b'9') {
+                    return Err(ParseError::new(ParseErrorKind::TypeMismatch));
+                }
+                let (number, parts) = lexer.num_string().validated().map_err(|_| malformed())?;
+                if !parts.is_int() {
+                    return Err(ParseError::new(ParseErrorKind::TypeMismatch));
+                }
+                let text: &str = number.as_ref();
+                text.parse::<$t>()
+                    .map_err(|_| ParseError::new(ParseErrorKind::NumberOutOfRange))
+            }
+        })*
+    };
+}
+
+impl_from_json_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Implement [`FromJson`] for floating-point types. Integers are accepted
+/// too; values too large for the type are rejected rather than becoming
+/// infinite.
+macro_rules! impl_from_json_float {
+    ($($t:ty),* $(,)?) => {
+        $(impl FromJson for $t {
+            fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+                if !matches!(next, b'-' | b'0'..=b'9') {
+                    return Err(ParseError::new(ParseErrorKind::TypeMismatch));
+                }
+                let (number, _parts) = lexer.num_string().validated().map_err(|_| malformed())?;
+                let text: &str = number.as_ref();
+                text.parse::<$t>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or(ParseError::new(ParseErrorKind::NumberOutOfRange))
+            }
+        })*
+    };
+}
+
+impl_from_json_float!(f32, f64);
+
+/// A JSON array of `T`.
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+        let mut items = Vec::new();
+        read_array(next, lexer, |next, lexer| {
+            items.push(T::from_json(next, lexer)?);
+            Ok(())
+        })?;
+        Ok(items)
+    }
+}
+
+/// A JSON array of at most `N` items of `T`, for fields whose length the
+/// device cannot afford to leave unbounded. Items beyond the capacity are
+/// skipped (without being decoded) rather than failing the whole value.
+impl<T: FromJson, const N: usize> FromJson for heapless::Vec<T, N> {
+    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+        let mut items = heapless::Vec::new();
+        read_array(next, lexer, |next, lexer| {
+            if items.is_full() {
+                return skip_value(next, lexer);
+            }
+            // Cannot fail: we just checked there is room.
+            let _ = items.push(T::from_json(next, lexer)?);
+            Ok(())
+        })?;
+        Ok(items)
+    }
+}
+
+/// A heap-allocated [`Vec`] that holds at most `N` items.
+///
+/// Unlike [`heapless::Vec`], which reserves room for all `N` items inline, this
+/// only allocates for the items actually present, so it suits model fields
+/// that are usually short but must never grow without bound (e.g. a task's
+/// labels). Items beyond the capacity are skipped (without being decoded)
+/// rather than failing the whole value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundedVec<T, const N: usize>(Vec<T>);
+
+impl<T, const N: usize> BoundedVec<T, N> {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T, const N: usize> Default for BoundedVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> core::ops::Deref for BoundedVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T: FromJson, const N: usize> FromJson for BoundedVec<T, N> {
+    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+        let mut items = Vec::new();
+        read_array(next, lexer, |next, lexer| {
+            if items.len() == N {
+                return skip_value(next, lexer);
+            }
+            items.push(T::from_json(next, lexer)?);
+            Ok(())
+        })?;
+        Ok(Self(items))
+    }
+}
+
+/// A JSON string of at most `N` bytes. Longer strings are truncated at the
+/// last whole character that fits rather than failing the whole value.
+impl<const N: usize> FromJson for heapless::String<N> {
+    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+        if next != b'"' {
+            return Err(ParseError::new(ParseErrorKind::TypeMismatch));
+        }
+        let value = lexer.discarded().str_string().map_err(|_| malformed())?;
+        let mut text = heapless::String::new();
+        for c in value.chars() {
+            if text.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(text)
+    }
+}
+
+/// Shorthand for the most common failure: input that is not valid JSON.
+fn malformed() -> ParseError {
+    ParseError::new(ParseErrorKind::MalformedJson)
+}
+
+/// Shorthand for a required object member that was never seen.
+pub(crate) fn missing(field: &'static str) -> ParseError {
+    ParseError::new(ParseErrorKind::MissingField(field))
+}
+
+/// Read a JSON object, invoking `on_field` for each member. When it is called,
+/// the lexer is positioned on the (already-peeked) field value passed as
+/// `value_next`, and `on_field` must consume exactly that value. The peeked
+/// `next` must be the opening `{`.
+///
+/// Errors returned by `on_field` are tagged with the member's key, so nested
+/// objects build up a dotted field path such as `due.date`.
+pub(crate) fn read_object<F>(
+    next: u8,
+    lexer: &mut SliceLexer<'_>,
+    mut on_field: F,
+) -> Result<(), ParseError>
+where
+    F: FnMut(&str, u8, &mut SliceLexer<'_>) -> Result<(), ParseError>,
+{
+    if next != b'{' {
+        return Err(ParseError::new(ParseErrorKind::TypeMismatch));
+    }
+    lexer
+        .discarded()
+        .seq(b'}', Lex::ws_peek, |key_next, lexer| {
+            if key_next != b'"' {
+                return Err(malformed());
+            }
+            let key = lexer.discarded().str_string().map_err(|_| malformed())?;
+            let key: &str = &key;
+            lexer.expect(Lex::ws_peek, b':').ok_or_else(malformed)?;
+            let value_next = lexer.ws_peek().ok_or_else(malformed)?;
+            on_field(key, value_next, lexer).map_err(|e| e.within(key))
+        })
+}
+
+/// Implement [`FromJson`] for a model struct from a list of its fields.
+///
+/// This generates a private builder struct (every field held as an
+/// `Option`, filled in as members are read) and a [`FromJson`] impl that reads
+/// the object member by member, skipping unknown keys, and then validates the
+/// result. Each field is written `name: Type`, optionally followed by:
+///
+/// - `= default` — used when the member is absent; without one the field is
+///   required and a missing member is a [`ParseErrorKind::MissingField`].
+///   Optional members are simply `Option<T>` fields defaulting to `None` (a
+///   JSON `null` decodes to `None` as well).
+///
+/// and optionally preceded by `#[json(rename = "key")]` when the JSON key
+/// differs from the field name.
+///
+/// ```ignore
+/// from_json_object! {
+///     /// Collects duration fields as they are parsed.
+///     TaskDuration => TaskDurationBuilder {
+///         amount: u32,
+///         #[json(rename = "unit")]
+///         unit_name: String = String::from("minute"),
+///         note: Option<String> = None,
+///     }
+/// }
+/// ```
+macro_rules! from_json_object {
+    (
+        $(#[$builder_meta:meta])*
+        $model:ident => $builder:ident {
+            $(
+                $(#[json(rename = $key:literal)])?
+                $field:ident : $ty:ty $(= $default:expr)?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$builder_meta])*
+        #[derive(Default)]
+        struct $builder {
+            $($field: Option<$ty>,)*
+        }
+
+        impl $builder {
+            /// Build the model, failing only if a required field was never
+            /// set.
+            fn build(self) -> Result<$model, $crate::json::ParseError> {
+                Ok($model {
+                    $($field: $crate::json::from_json_object!(
+                        @value self.$field, $crate::json::from_json_object!(@key $field $($key)?)
+                        $(, $default)?
+                    ),)*
+                })
+            }
+        }
+
+        impl $crate::json::FromJson for $model {
+            fn from_json(
+                next: u8,
+                lexer: &mut hifijson::SliceLexer<'_>,
+            ) -> Result<Self, $crate::json::ParseError> {
+                let mut builder = $builder::default();
+                $crate::json::read_object(next, lexer, |key, value_next, lexer| {
+                    match key {
+                        $($crate::json::from_json_object!(@key $field $($key)?) => {
+                            builder.$field = Some(
+                                $crate::json::FromJson::from_json(value_next, lexer)?,
+                            )
+                        })*
+                        _ => $crate::json::skip_value(value_next, lexer)?,
+                    }
+                    Ok(())
+                })?;
+                builder.build()
+            }
+        }
+    };
+
+    (@key $field:ident) => { stringify!($field) };
+    (@key $field:ident $key:literal) => { $key };
+
+    (@value $value:expr, $key:expr) => {
+        $value.ok_or_else(|| $crate::json::missing($key))?
+    };
+    (@value $value:expr, $key:expr, $default:expr) => {
+        $value.unwrap_or_else(|| $default)
+    };
+}
+
+pub(crate) use from_json_object;
+
+/// Read a JSON array, invoking `on_item` for each element. When it is called,
+/// the lexer is positioned on the (already-peeked) element passed as `next`,
+/// and `on_item` must consume exactly that value. The peeked `next` must be the
+/// opening `[`.
+pub(crate) fn read_array<F>(
+    next: u8,
+    lexer: &mut SliceLexer<'_>,
+    on_item: F,
+) -> Result<(), ParseError>
+where
+    F: FnMut(u8, &mut SliceLexer<'_>) -> Result<(), ParseError>,
+{
+    if next != b'[' {
+        return Err(ParseError::new(ParseErrorKind::TypeMismatch));
+    }
+    lexer.discarded().seq(b']', Lex::ws_peek, on_item)
+}
+
+/// Recursively discard a single JSON value of any type.
+pub(crate) fn skip_value(next: u8, lexer: &mut SliceLexer<'_>) -> Result<(), ParseError> {
+    match next {
+        b'"' => {
+            lexer.discarded().str_ignore().map_err(|_| malformed())?;
+        }
+        b'-' | b'0'..=b'9' => {
+            lexer.num_ignore().validate().map_err(|_| malformed())?;
+        }
+        b't' | b'f' | b'n' => {
+            lexer.null_or_bool().ok_or_else(malformed)?;
+        }
+        b'[' => read_array(next, lexer, skip_value)?,
+        b'{' => {
+            read_object(next, lexer, |_key, value_next, lexer| {
+                skip_value(value_next, lexer)
+            })?;
+        }
+        _ => return Err(malformed()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    /// Feed `json` to a parser for `path` one byte at a time (exercising every
+    /// chunk boundary), returning the elements and the parser.
+    fn parse_bytewise<T: FromJson>(
+        path: &'static [&'static str],
+        json: &[u8],
+    ) -> Result<(Vec<T>, StreamArrayParser<T>), ParseError> {
+        let mut parser = StreamArrayParser::with_path(path);
+        let mut out = Vec::new();
+        for &byte in json {
+            parser.feed(&[byte], &mut out)?;
+        }
+        parser.finish()?;
+        Ok((out, parser))
+    }
+
+    #[test]
+    fn parses_nested_key_path() {
+        let json = br#"{"sync_token":"abc","sync":{"projects":[1],"items":["a","b\"c"]},"items":["wrong"]}"#;
+        let (items, _) = parse_bytewise::<String>(&["sync", "items"], json).expect("valid json");
+        assert_eq!(items, ["a", "b\"c"]);
+    }
+
+    #[test]
+    fn ignores_matching_keys_off_the_path() {
+        // `items` nested inside another member, or inside an array, is not the
+        // top-level `items` the path names.
+        let json = br#"{"meta":{"items":[1]},"list":[{"items":[2]}],"items":[3, 4]}"#;
+        let (items, _) = parse_bytewise::<u32>(&["items"], json).expect("valid json");
+        assert_eq!(items, [3, 4]);
+    }
+
+    #[test]
+    fn parses_bare_top_level_array() {
+        let json = br#" [ 1, -2 ,3,{"ignored":true}] "#;
+        let mut parser = StreamArrayParser::<Option<i32>>::with_path(&[]);
+        let mut out = Vec::new();
+        let err = parser.feed(json, &mut out).unwrap_err();
+        // The object element is not an integer.
+        assert_eq!(err.kind(), ParseErrorKind::TypeMismatch);
+        assert_eq!(err.index(), Some(3));
+        assert_eq!(out, [Some(1), Some(-2), Some(3)]);
+
+        let (out, _) = parse_bytewise::<bool>(&[], b"[true,false]").expect("valid json");
+        assert_eq!(out, [true, false]);
+    }
+
+    #[test]
+    fn parses_nested_arrays_as_elements() {
+        let json = br#"{"results":[{"a":[1,{"b":"]"}]},{}]}"#;
+        let mut parser = StreamArrayParser::<Count>::new();
+        let mut out = Vec::new();
+        parser.feed(json, &mut out).expect("valid json");
+        assert_eq!(out, [Count(1), Count(0)]);
+    }
+
+    #[test]
+    fn captures_cursor_only_at_the_top_level() {
+        let json = br#"{"meta":{"next_cursor":"nested"},"results":[],"next_cursor":"top"}"#;
+        let (_, parser) = parse_bytewise::<u8>(RESULTS_PATH, json).expect("valid json");
+        assert_eq!(parser.next_cursor(), Some("top"));
+    }
+
+    #[test]
+    fn rejects_a_response_cut_short() {
+        let json = br#"{"results":[1,2],"next_cursor":"page-2"}"#;
+        // Anywhere short of the final `}`, including between elements and
+        // before the cursor arrives.
+        for end in [0, 1, 12, 13, 16, 17, 30, json.len() - 1] {
+            let err = parse_bytewise::<u8>(RESULTS_PATH, &json[..end])
+                .err()
+                .expect("parse error");
+            assert_eq!(err.kind(), ParseErrorKind::MalformedJson, "{end}");
+            assert_eq!(err.offset(), Some(end), "{end}");
+        }
+        let (out, parser) = parse_bytewise::<u8>(RESULTS_PATH, json).expect("valid json");
+        assert_eq!(out, [1, 2]);
+        assert_eq!(parser.next_cursor(), Some("page-2"));
+
+        let (_, parser) = parse_bytewise::<u8>(&[], b"[]").expect("valid json");
+        assert!(parser.finish().is_ok());
+        assert!(parse_bytewise::<u8>(&[], b"[1").is_err());
+    }
+
+    #[test]
+    fn rejects_a_path_through_a_non_object() {
+        let json = br#"{"sync":[{"items":[]}]}"#;
+        let err = parse_bytewise::<u8>(&["sync", "items"], json)
+            .err()
+            .expect("parse error");
+        assert_eq!(err.kind(), ParseErrorKind::TypeMismatch);
+        assert_eq!(err.field(), Some("sync"));
+        assert_eq!(err.offset(), Some(8));
+    }
+
+    #[test]
+    fn primitive_types_reject_mismatched_values() {
+        let kind = |json: &[u8]| {
+            parse_bytewise::<u8>(&[], json)
+                .err()
+                .expect("parse error")
+                .kind()
+        };
+        assert_eq!(kind(b"[\"1\"]"), ParseErrorKind::TypeMismatch);
+        assert_eq!(kind(b"[1.5]"), ParseErrorKind::TypeMismatch);
+        assert_eq!(kind(b"[256]"), ParseErrorKind::NumberOutOfRange);
+        assert_eq!(kind(b"[-]"), ParseErrorKind::MalformedJson);
+    }
+
+    #[test]
+    fn parses_arrays_and_floats() {
+        let json = br#"[[1.5, -2, 3e2], [], [0.25]]"#;
+        let (out, _) = parse_bytewise::<Vec<f64>>(&[], json).expect("valid json");
+        assert_eq!(out, [vec![1.5, -2.0, 300.0], vec![], vec![0.25]]);
+
+        let kind = |json: &[u8]| parse_bytewise::<f32>(&[], json).err().unwrap().kind();
+        assert_eq!(kind(b"[1e39]"), ParseErrorKind::NumberOutOfRange);
+        assert_eq!(kind(b"[true]"), ParseErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn array_errors_name_the_field() {
+        let json = br#"[{"labels":["a",1]}]"#;
+        let err = parse_bytewise::<Labels>(&[], json).err().unwrap();
+        assert_eq!(err.kind(), ParseErrorKind::TypeMismatch);
+        assert_eq!(err.field(), Some("labels"));
+    }
+
+    #[test]
+    fn bounded_vec_drops_excess_items() {
+        let json = br#"[["a","b","c",{"skipped":[1]}], ["d"]]"#;
+        let (out, _) = parse_bytewise::<heapless::Vec<String, 2>>(&[], json).expect("valid json");
+        assert_eq!(out[0], ["a", "b"]);
+        assert_eq!(out[1], ["d"]);
+    }
+
+    #[test]
+    fn bounded_string_truncates_at_a_char_boundary() {
+        // "é" is two bytes, so only two of them fit in five bytes.
+        let (out, _) = parse_bytewise::<heapless::String<5>>(&[], "[\"ééé\", \"ok\"]".as_bytes())
+            .expect("valid json");
+        assert_eq!(out[0], "éé");
+        assert_eq!(out[1], "ok");
+    }
+
+    #[test]
+    fn object_macro_maps_fields() {
+        let json = br#"[{"name":"x","n":"ignored","count":3,"nested":{"name":"y"},"note":null}]"#;
+        let (out, _) = parse_bytewise::<Model>(&[], json).expect("valid json");
+        assert_eq!(
+            out,
+            [Model {
+                name: "x".to_string(),
+                count: 3,
+                nested: Some(Inner {
+                    label: "y".to_string()
+                }),
+                note: None,
+            }]
+        );
+
+        // Defaults and optional fields may be omitted entirely.
+        let (out, _) = parse_bytewise::<Model>(&[], br#"[{"name":"z"}]"#).expect("valid json");
+        assert_eq!(out[0].count, 7);
+        assert_eq!(out[0].nested, None);
+    }
+
+    #[test]
+    fn object_macro_reports_missing_renamed_field() {
+        let json = br#"[{"name":"x","nested":{"label":"wrong key"}}]"#;
+        let err = parse_bytewise::<Model>(&[], json).err().unwrap();
+        assert_eq!(err.kind(), ParseErrorKind::MissingField("name"));
+        assert_eq!(err.field(), Some("nested"));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Model {
+        name: String,
+        count: u32,
+        nested: Option<Inner>,
+        note: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Inner {
+        label: String,
+    }
+
+    from_json_object! {
+        Model => ModelBuilder {
+            name: String,
+            count: u32 = 7,
+            nested: Option<Inner> = None,
+            note: Option<String> = None,
+        }
+    }
+
+    from_json_object! {
+        Inner => InnerBuilder {
+            #[json(rename = "name")]
+            label: String,
+        }
+    }
+
+    #[test]
+    fn bounded_heap_vec_drops_excess_items() {
+        let json = br#"[[1,2,3,"skipped"],[4]]"#;
+        let (out, _) = parse_bytewise::<BoundedVec<u8, 2>>(&[], json).expect("valid json");
+        assert_eq!(*out[0], [1, 2]);
+        assert_eq!(out[1].clone().into_inner(), [4]);
+    }
+
+    /// An object with a single array member, for exercising nested errors.
+    #[derive(Debug)]
+    struct Labels;
+
+    impl FromJson for Labels {
+        fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+            read_object(next, lexer, |_key, value_next, lexer| {
+                Vec::<String>::from_json(value_next, lexer).map(drop)
+            })?;
+            Ok(Labels)
+        }
+    }
+
+    /// Counts an object's members, skipping their values.
+    #[derive(Debug, PartialEq)]
+    struct Count(usize);
+
+    impl FromJson for Count {
+        fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
+            let mut count = 0;
+            read_object(next, lexer, |_key, value_next, lexer| {
+                count += 1;
+                skip_value(value_next, lexer)
+            })?;
+            Ok(Count(count))
+        }
+    }
+
+    #[test]
+    fn parse_error_truncates_long_field_paths() {
+        let err = ParseError::new(ParseErrorKind::TypeMismatch)
+            .within("a_rather_long_inner_field_name")
+            .within("outer");
+        assert_eq!(err.field(), Some("outer.a_rather_long_inner_field_"));
+    }
+
+    #[test]
+    fn display_includes_location() {
+        let err = missing("id").within("due").located(42, Some(3));
+        assert_eq!(
+            err.to_string(),
+            "missing field `id` in `due` of element #3 at byte 42"
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Fitting and wrapping text to a pixel width.
+//!
+//! The panel fonts are proportional, so how much of a title fits its column
+//! depends on the glyphs rather than on a character count. The glyph advances
+//! come from the caller (the firmware asks its u8g2 fonts), which keeps the
+//! layout itself pure and testable on the host.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::markdown::{RichText, SpanStyle, ELLIPSIS};
+
+/// Glyph widths of the fonts text is drawn in.
+pub trait TextMetrics {
+    /// How far, in pixels, drawing `c` in `style` moves the pen.
+    fn advance(&self, c: char, style: SpanStyle) -> u32;
+}
+
+impl<F: Fn(char, SpanStyle) -> u32> TextMetrics for F {
+    fn advance(&self, c: char, style: SpanStyle) -> u32 {
+        self(c, style)
+    }
+}
+
+/// The width of `text` in pixels.
+pub fn width(text: &RichText, metrics: &impl TextMetrics) -> u32 {
+    text.spans()
+        .iter()
+        .flat_map(|span| span.text.chars().map(|c| metrics.advance(c, span.style)))
+        .sum()
+}
+
+/// Shorten `text` to fit within `max_width` pixels.
+///
+/// Text that fits is returned unchanged. Otherwise it is cut after the last
+/// whole word that leaves room for an ellipsis, or mid-word if even the first
+/// word is too wide. If not even the ellipsis fits, the result is empty.
+pub fn fit(text: &RichText, max_width: u32, metrics: &impl TextMetrics) -> RichText {
+    if width(text, metrics) <= max_width {
+        return text.clone();
+    }
+    let ellipsis: u32 = ELLIPSIS
+        .chars()
+        .map(|c| metrics.advance(c, SpanStyle::default()))
+        .sum();
+    let Some(budget) = max_width.checked_sub(ellipsis) else {
+        return RichText::default();
+    };
+
+    // Cuts as (run, byte offset): the longest prefix within the budget, and
+    // the longest one ending just before a space.
+    let mut longest = (0, 0);
+    let mut word = None;
+    let mut used = 0;
+    'runs: for (i, span) in text.spans().iter().enumerate() {
+        for (at, c) in span.text.char_indices() {
+            if c.is_whitespace() {
+                word = Some((i, at));
+            }
+            used += metrics.advance(c, span.style);
+            if used > budget {
+                break 'runs;
+            }
+            longest = (i, at + c.len_utf8());
+        }
+    }
+
+    let mut fitted = RichText::default();
+    if let Some((span, at)) = word {
+        fitted = text.prefix(span, at);
+        fitted.trim_end();
+    }
+    if fitted.is_empty() {
+        fitted = text.prefix(longest.0, longest.1);
+        fitted.trim_end();
+    }
+    fitted.push(ELLIPSIS, SpanStyle::default());
+    fitted
+}
+
+/// Break `text` into at most `max_lines` lines of at most `max_width` pixels.
+///
+/// Lines break at spaces, or mid-word when a word is wider than a whole line,
+/// and always at a newline. Text that needs more lines than allowed has its
+/// last line cut short with an ellipsis, as by [`fit`]. Empty text has no
+/// lines.
+pub fn wrap(
+    text: &RichText,
+    max_width: u32,
+    max_lines: usize,
+    metrics: &impl TextMetrics,
+) -> Vec<RichText> {
+    let glyphs: Vec<(char, SpanStyle)> = text
+        .spans()
+        .iter()
+        .flat_map(|span| span.text.chars().map(move |c| (c, span.style)))
+        .collect();
+    let advance = |&(c, style): &(char, SpanStyle)| metrics.advance(c, style);
+
+    let mut lines: Vec<Range<usize>> = Vec::new();
+    let mut start = 0;
+    let mut width = 0;
+    // The last space on the current line, where it can break.
+    let mut space = None;
+    let mut i = 0;
+    while i < glyphs.len() && lines.len() <= max_lines {
+        let (c, _) = glyphs[i];
+        if c == '\n' {
+            lines.push(start..i);
+            (start, width, space) = (i + 1, 0, None);
+            i += 1;
+            continue;
+        }
+
+        let w = advance(&glyphs[i]);
+        if width + w > max_width && i > start {
+            let end = if c.is_whitespace() {
+                i
+            } else {
+                space.unwrap_or(i)
+            };
+            lines.push(start..end);
+            // Wrapped lines don't start with the spaces they broke at.
+            start = end;
+            while glyphs.get(start).is_some_and(|&(c, _)| c == ' ') {
+                start += 1;
+            }
+            width = glyphs[start.min(i)..i].iter().map(advance).sum();
+            space = None;
+            i = i.max(start);
+            continue;
+        }
+
+        if c.is_whitespace() {
+            space = Some(i);
+        }
+        width += w;
+        i += 1;
+    }
+    if start < glyphs.len() {
+        lines.push(start..glyphs.len());
+    }
+
+    let mut wrapped: Vec<RichText> = lines
+        .iter()
+        .take(max_lines)
+        .map(|line| collect(&glyphs[line.clone()]))
+        .collect();
+    if lines.len() > max_lines {
+        if let Some(last) = wrapped.last_mut() {
+            // Run the rest of the text on, to be cut short with an ellipsis.
+            let rest = lines[max_lines - 1].start..glyphs.len();
+            *last = fit(&collect(&glyphs[rest]), max_width, metrics);
+        }
+    }
+    for line in &mut wrapped {
+        line.trim_end();
+    }
+    wrapped
+}
+
+/// Build text from styled characters, with newlines as spaces.
+fn collect(glyphs: &[(char, SpanStyle)]) -> RichText {
+    let mut text = RichText::default();
+    for &(c, style) in glyphs {
+        let c = if c == '\n' { ' ' } else { c };
+        text.push(c.encode_utf8(&mut [0; 4]), style);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::parse_spans;
+    use alloc::string::{String, ToString};
+
+    /// A monospaced font: every glyph is 6 pixels wide.
+    fn mono(_: char, _: SpanStyle) -> u32 {
+        6
+    }
+
+    /// A proportional font where bold text is wider.
+    fn proportional(c: char, style: SpanStyle) -> u32 {
+        let width = match c {
+            'i' | 'l' | '.' | ' ' => 3,
+            'm' | 'w' => 9,
+            _ => 6,
+        };
+        width + u32::from(style.bold)
+    }
+
+    fn fit_str(src: &str, max_width: u32, metrics: &impl TextMetrics) -> String {
+        fit(&parse_spans(src, usize::MAX), max_width, metrics).to_string()
+    }
+
+    #[test]
+    fn measures_each_glyph_in_its_style() {
+        assert_eq!(width(&parse_spans("mill", usize::MAX), &proportional), 18);
+        assert_eq!(
+            width(&parse_spans("**mill**", usize::MAX), &proportional),
+            22
+        );
+        assert_eq!(width(&RichText::default(), &proportional), 0);
+    }
+
+    #[test]
+    fn keeps_text_that_fits() {
+        assert_eq!(fit_str("Buy milk", 48, &mono), "Buy milk");
+        assert_eq!(fit_str("", 0, &mono), "");
+    }
+
+    #[test]
+    fn cuts_at_a_word_boundary() {
+        // Ten characters of room leave seven for text before the ellipsis.
+        assert_eq!(fit_str("Buy oat milk today", 60, &mono), "Buy oat...");
+        assert_eq!(fit_str("Buy oat milk today", 59, &mono), "Buy...");
+        // Narrow glyphs let more of the text in.
+        assert_eq!(
+            fit_str("lil will fill it all", 60, &proportional),
+            "lil will fill..."
+        );
+    }
+
+    #[test]
+    fn cuts_mid_word_when_the_first_word_is_too_wide() {
+        assert_eq!(fit_str("Supercalifragilistic", 60, &mono), "Superca...");
+        assert_eq!(fit_str("A Supercalifragilistic", 60, &mono), "A...");
+    }
+
+    #[test]
+    fn keeps_styles_and_cuts_on_char_boundaries() {
+        let fitted = fit(&parse_spans("**Café** crème brûlée", usize::MAX), 78, &mono);
+        assert_eq!(fitted.to_string(), "Café crème...");
+        assert!(fitted.spans()[0].style.bold);
+        assert_eq!(fit_str("日本語のタスク", 36, &mono), "日本語...");
+    }
+
+    #[test]
+    fn gives_up_when_not_even_the_ellipsis_fits() {
+        assert_eq!(fit_str("Buy milk", 17, &mono), "");
+        assert_eq!(fit_str("Buy milk", 18, &mono), "...");
+    }
+
+    fn wrap_str(src: &str, max_width: u32, max_lines: usize) -> Vec<String> {
+        wrap(&parse_spans(src, usize::MAX), max_width, max_lines, &mono)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        // Ten characters to a line.
+        assert_eq!(
+            wrap_str("Pick up the dry cleaning on the way home", 60, 9),
+            ["Pick up", "the dry", "cleaning", "on the way", "home"]
+        );
+        assert_eq!(wrap_str("fits", 60, 9), ["fits"]);
+        assert!(wrap_str("", 60, 9).is_empty());
+    }
+
+    #[test]
+    fn breaks_words_wider_than_a_line() {
+        assert_eq!(
+            wrap_str("see https://example.com/a/really/long/path ok", 60, 9),
+            ["see", "example.co", "m ok"]
+        );
+        assert_eq!(
+            wrap_str("abcdefghijklmnopqrstuvwxyz", 60, 9),
+            ["abcdefghij", "klmnopqrst", "uvwxyz"]
+        );
+    }
+
+    #[test]
+    fn breaks_at_newlines_and_drops_wrapped_spaces() {
+        let text = RichText::plain("one\n\nthree   spaced   out");
+        let lines: Vec<String> = wrap(&text, 60, 9, &mono)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(lines, ["one", "", "three", "spaced", "out"]);
+    }
+
+    #[test]
+    fn ellipsizes_the_last_allowed_line() {
+        assert_eq!(
+            wrap_str("Pick up the dry cleaning on the way home", 60, 2),
+            ["Pick up", "the dry..."]
+        );
+        assert_eq!(
+            wrap_str("Pick up the dry cleaning on the way home", 60, 1),
+            ["Pick up..."]
+        );
+        assert!(wrap_str("Pick up", 60, 0).is_empty());
+    }
+
+    #[test]
+    fn keeps_styles_across_lines() {
+        let lines = wrap(
+            &parse_spans("**bold words** then plain", usize::MAX),
+            60,
+            9,
+            &mono,
+        );
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].to_string(), "bold words");
+        assert!(lines[0].spans()[0].style.bold);
+        assert!(lines[1].spans()[0].style.is_plain());
+    }
+}
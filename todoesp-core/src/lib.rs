@@ -12,17 +12,39 @@
 
 extern crate alloc;
 
+pub mod backoff;
+pub mod clock;
+pub mod codec;
 pub mod colour;
 pub mod hash;
+pub mod http;
+pub mod json;
+pub mod layout;
 pub mod markdown;
+pub mod ntp;
 pub mod snapshot;
 pub mod task;
 pub mod time;
+pub mod user;
 
+pub use backoff::Backoff;
+pub use clock::{RtcClock, SyncPolicy};
+pub use codec::{
+    decode_record, decode_tasks, encode_record, encode_tasks, DecodeError, TaskRecord,
+};
 pub use colour::Colour;
-pub use hash::{fingerprint_status, fingerprint_tasks};
+pub use hash::{fingerprint_offline, fingerprint_status, fingerprint_tasks};
+pub use http::{parse_http_date, parse_retry_after, FetchFailure, MAX_RETRY_AFTER_SECS};
+pub use json::{parse_json, BoundedVec, FromJson, ParseError, ParseErrorKind, StreamArrayParser};
+pub use layout::TextMetrics;
+pub use markdown::{parse_spans, RichText, Span, SpanStyle};
 pub use snapshot::{get_setup_tasks, SetupState, TaskSnapshot};
 pub use task::{
-    parse_tasks, FromJson, ParseError, Task, TaskDue, TaskDueState, TaskDuration, TaskStreamParser,
+    next_state_change, parse_tasks, sort_tasks, Task, TaskDeadline, TaskDeadlineState, TaskDue,
+    TaskDueState, TaskDuration, TaskStreamParser, DEADLINE_APPROACHING_DAYS, MAX_DESCRIPTION_LEN,
+    MAX_LABELS,
 };
-pub use time::{local_from_unix, offset_from_seconds};
+#[cfg(feature = "tzdb")]
+pub use time::local_in_zone;
+pub use time::{local_from_unix, offset_from_seconds, parse_utc_offset, PosixTz};
+pub use user::{parse_user_profile, UserProfile};
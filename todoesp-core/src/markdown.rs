@@ -1,7 +1,512 @@
-//! Minimal Markdown stripping used to render Todoist task content as plain text.
+//! Minimal Markdown parsing used to render Todoist task content, either as
+//! plain text ([`strip`]) or as styled runs ([`parse_spans`]).
+//!
+//! Besides the usual emphasis, code and links, it understands the extensions
+//! Todoist itself renders: `!!highlights!!`, bare URLs (shown as their host),
+//! `todoist://` app links, `@mentions`, `:shortcode:` emoji (shown as a text
+//! fallback, since the panel fonts have no emoji) and backslash escapes.
+//!
+//! The source is scanned once, left to right. Markup that is never closed is
+//! kept as literal text. Looking for a closing delimiter searches forward from
+//! the opener, and each delimiter's last position is remembered, so a title
+//! full of stray `*` or `[` is still handled in linear time.
 
-use alloc::format;
-use alloc::string::String;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+/// Appended to text cut short by [`strip`].
+pub(crate) const ELLIPSIS: &str = "...";
+
+/// How a run of text is emphasised. Markup nests, so several can be set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpanStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub strikethrough: bool,
+    pub code: bool,
+    pub link: bool,
+    pub highlight: bool,
+    pub mention: bool,
+}
+
+impl SpanStyle {
+    const BOLD: Self = Self {
+        bold: true,
+        ..Self::PLAIN
+    };
+    const ITALIC: Self = Self {
+        italic: true,
+        ..Self::PLAIN
+    };
+    const STRIKETHROUGH: Self = Self {
+        strikethrough: true,
+        ..Self::PLAIN
+    };
+    const CODE: Self = Self {
+        code: true,
+        ..Self::PLAIN
+    };
+    const LINK: Self = Self {
+        link: true,
+        ..Self::PLAIN
+    };
+    const HIGHLIGHT: Self = Self {
+        highlight: true,
+        ..Self::PLAIN
+    };
+    const MENTION: Self = Self {
+        mention: true,
+        ..Self::PLAIN
+    };
+    const PLAIN: Self = Self {
+        bold: false,
+        italic: false,
+        strikethrough: false,
+        code: false,
+        link: false,
+        highlight: false,
+        mention: false,
+    };
+
+    /// No emphasis at all.
+    pub fn is_plain(&self) -> bool {
+        *self == Self::PLAIN
+    }
+
+    /// The styles of both `self` and `other`.
+    fn with(self, other: Self) -> Self {
+        Self {
+            bold: self.bold || other.bold,
+            italic: self.italic || other.italic,
+            strikethrough: self.strikethrough || other.strikethrough,
+            code: self.code || other.code,
+            link: self.link || other.link,
+            highlight: self.highlight || other.highlight,
+            mention: self.mention || other.mention,
+        }
+    }
+
+    /// The styles packed one per bit, for hashing.
+    pub(crate) fn bits(self) -> u8 {
+        u8::from(self.bold)
+            | u8::from(self.italic) << 1
+            | u8::from(self.strikethrough) << 2
+            | u8::from(self.code) << 3
+            | u8::from(self.link) << 4
+            | u8::from(self.highlight) << 5
+            | u8::from(self.mention) << 6
+    }
+}
+
+/// A run of text drawn in one style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+/// Text made of styled runs. Adjacent runs always differ in style, and none is
+/// empty. Formats (and so converts to a string) as its plain text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RichText(Vec<Span>);
+
+impl RichText {
+    /// Unstyled text.
+    pub fn plain(text: impl Into<String>) -> Self {
+        let mut rich = Self::default();
+        rich.push(&text.into(), SpanStyle::PLAIN);
+        rich
+    }
+
+    pub fn spans(&self) -> &[Span] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Append `text`, merging it into the last run if the style matches.
+    pub(crate) fn push(&mut self, text: &str, style: SpanStyle) {
+        if text.is_empty() {
+            return;
+        }
+        match self.0.last_mut() {
+            Some(last) if last.style == style => last.text.push_str(text),
+            _ => self.0.push(Span {
+                text: text.to_string(),
+                style,
+            }),
+        }
+    }
+
+    /// The text of the runs before `span`, plus the first `at` bytes of run
+    /// `span`.
+    pub(crate) fn prefix(&self, span: usize, at: usize) -> Self {
+        let mut prefix = Self(self.0[..span].to_vec());
+        if let Some(last) = self.0.get(span) {
+            prefix.push(&last.text[..at], last.style);
+        }
+        prefix
+    }
+
+    /// Remove trailing whitespace, and any runs left empty.
+    pub(crate) fn trim_end(&mut self) {
+        while let Some(last) = self.0.last_mut() {
+            last.text.truncate(last.text.trim_end().len());
+            if !last.text.is_empty() {
+                break;
+            }
+            self.0.pop();
+        }
+    }
+
+    /// Cut the text to at most `max_len` characters, ending in an unstyled
+    /// ellipsis when there is room for one.
+    fn truncate(&mut self, max_len: usize) {
+        let len: usize = self.0.iter().map(|span| span.text.chars().count()).sum();
+        if len <= max_len {
+            return;
+        }
+        let ellipsis = max_len >= ELLIPSIS.len();
+        let mut left = if ellipsis {
+            max_len - ELLIPSIS.len()
+        } else {
+            max_len
+        };
+
+        let mut kept = 0;
+        for span in &mut self.0 {
+            if let Some((cut, _)) = span.text.char_indices().nth(left) {
+                span.text.truncate(cut);
+                if cut > 0 {
+                    kept += 1;
+                }
+                break;
+            }
+            left -= span.text.chars().count();
+            kept += 1;
+        }
+        self.0.truncate(kept);
+
+        if ellipsis {
+            self.push(ELLIPSIS, SpanStyle::PLAIN);
+        }
+    }
+}
+
+impl fmt::Display for RichText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|span| f.write_str(&span.text))
+    }
+}
+
+/// A delimiter that closes a construct, indexing [`Parser::found`].
+#[derive(Debug, Clone, Copy)]
+enum Closer {
+    DoubleStar,
+    DoubleUnderscore,
+    DoubleTilde,
+    DoubleBang,
+    Star,
+    Underscore,
+    Tilde,
+    Backtick,
+    Bracket,
+    Paren,
+}
+
+impl Closer {
+    const COUNT: usize = 10;
+
+    fn pattern(self) -> &'static str {
+        match self {
+            Self::DoubleStar => "**",
+            Self::DoubleUnderscore => "__",
+            Self::DoubleTilde => "~~",
+            Self::DoubleBang => "!!",
+            Self::Star => "*",
+            Self::Underscore => "_",
+            Self::Tilde => "~",
+            Self::Backtick => "`",
+            Self::Bracket => "]",
+            Self::Paren => ")",
+        }
+    }
+
+    /// The style of the text this delimiter closes.
+    fn style(self) -> SpanStyle {
+        match self {
+            Self::DoubleStar | Self::DoubleUnderscore => SpanStyle::BOLD,
+            Self::Star | Self::Underscore => SpanStyle::ITALIC,
+            Self::DoubleTilde | Self::Tilde => SpanStyle::STRIKETHROUGH,
+            Self::DoubleBang => SpanStyle::HIGHLIGHT,
+            Self::Backtick => SpanStyle::CODE,
+            Self::Bracket | Self::Paren => SpanStyle::PLAIN,
+        }
+    }
+}
+
+/// A construct recognised by [`Parser::markup`].
+struct Markup {
+    /// The text it stands for.
+    inner: Inner,
+    /// The byte offset just past its end.
+    next: usize,
+    /// The style it adds to its text.
+    style: SpanStyle,
+}
+
+/// The text a [`Markup`] stands for.
+enum Inner {
+    /// Source text that may hold further markup.
+    Parsed(Range<usize>),
+    /// Source text taken as it is, such as code or an escaped character.
+    Literal(Range<usize>),
+    /// Text replacing the construct, such as an emoji fallback.
+    Text(&'static str),
+}
+
+/// URL schemes recognised as bare links.
+const URL_SCHEMES: [&str; 2] = ["https://", "http://"];
+/// The scheme of links that open the Todoist app.
+const TODOIST_SCHEME: &str = "todoist://";
+/// Punctuation that ends a sentence rather than a bare URL.
+const URL_TRAILING: &[char] = &['.', ',', ';', ':', '!', '?', ')', '\'', '"'];
+/// The longest emoji shortcode name looked up.
+const MAX_SHORTCODE_LEN: usize = 32;
+
+/// Text fallbacks for common emoji shortcodes, by name.
+const SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "+1"),
+    ("-1", "-1"),
+    ("arrow_left", "<-"),
+    ("arrow_right", "->"),
+    ("bulb", "(idea)"),
+    ("calendar", "(calendar)"),
+    ("check", "(v)"),
+    ("cry", ":'("),
+    ("disappointed", ":("),
+    ("exclamation", "!"),
+    ("fire", "(fire)"),
+    ("grinning", ":D"),
+    ("heart", "<3"),
+    ("heavy_check_mark", "(v)"),
+    ("hourglass", "(wait)"),
+    ("memo", "(note)"),
+    ("pushpin", "(pin)"),
+    ("question", "?"),
+    ("rocket", "(rocket)"),
+    ("slightly_smiling_face", ":)"),
+    ("smile", ":)"),
+    ("star", "*"),
+    ("tada", "(party)"),
+    ("thumbsdown", "-1"),
+    ("thumbsup", "+1"),
+    ("warning", "(!)"),
+    ("white_check_mark", "(v)"),
+    ("wink", ";)"),
+    ("x", "(x)"),
+];
+
+struct Parser<'a> {
+    src: &'a str,
+    /// The result of the last search for each [`Closer`]: where it started,
+    /// and where the delimiter was found (`None` if it doesn't occur again).
+    found: [Option<(usize, Option<usize>)>; Closer::COUNT],
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            found: [None; Closer::COUNT],
+        }
+    }
+
+    /// Append the text of `src[start..end]`, nested inside markup that gives
+    /// it `style`, to `out`.
+    fn walk(&mut self, start: usize, end: usize, style: SpanStyle, out: &mut RichText) {
+        let mut pos = start;
+        let mut plain = start;
+        while let Some(c) = self.src[pos..end].chars().next() {
+            match self.markup(pos, end) {
+                Some(markup) => {
+                    out.push(&self.src[plain..pos], style);
+                    let inner_style = style.with(markup.style);
+                    match markup.inner {
+                        Inner::Parsed(inner) => self.walk(inner.start, inner.end, inner_style, out),
+                        Inner::Literal(inner) => out.push(&self.src[inner], inner_style),
+                        Inner::Text(text) => out.push(text, inner_style),
+                    }
+                    pos = markup.next;
+                    plain = pos;
+                }
+                None => pos += c.len_utf8(),
+            }
+        }
+        out.push(&self.src[plain..end], style);
+    }
+
+    /// Recognise a construct opening at `pos` and closed before `end`.
+    fn markup(&mut self, pos: usize, end: usize) -> Option<Markup> {
+        let rest = &self.src[pos..end];
+        let (closer, open) = match rest.as_bytes() {
+            [b'*', b'*', ..] => (Closer::DoubleStar, 2),
+            [b'_', b'_', ..] => (Closer::DoubleUnderscore, 2),
+            [b'~', b'~', ..] => (Closer::DoubleTilde, 2),
+            [b'!', b'!', ..] => (Closer::DoubleBang, 2),
+            [b'*', ..] => (Closer::Star, 1),
+            [b'_', ..] => (Closer::Underscore, 1),
+            [b'~', ..] => (Closer::Tilde, 1),
+            [b'`', ..] => (Closer::Backtick, 1),
+            [b'!', b'[', ..] => return self.link(pos + 2, end, SpanStyle::PLAIN),
+            [b'[', ..] => return self.link(pos + 1, end, SpanStyle::LINK),
+            [b'\\', c, ..] if c.is_ascii_punctuation() => {
+                return Some(Markup {
+                    inner: Inner::Literal(pos + 1..pos + 2),
+                    next: pos + 2,
+                    style: SpanStyle::PLAIN,
+                });
+            }
+            [b'@', ..] if !self.in_word(pos) => return self.mention(pos, end),
+            [b':', ..] => return self.shortcode(pos, end),
+            [b'h' | b't', ..] if !self.in_word(pos) => return self.bare_url(pos, end),
+            _ => return None,
+        };
+
+        // Underscores inside a word, as in `snake_case`, are not emphasis.
+        if rest.starts_with('_') && self.in_word(pos) {
+            return None;
+        }
+
+        let inner = pos + open;
+        let close = self.find(closer, inner, end)?;
+        let inner = match closer {
+            Closer::Backtick => Inner::Literal(inner..close),
+            _ => Inner::Parsed(inner..close),
+        };
+        Some(Markup {
+            inner,
+            next: close + closer.pattern().len(),
+            style: closer.style(),
+        })
+    }
+
+    /// Whether `pos` directly follows a letter or digit.
+    fn in_word(&self, pos: usize) -> bool {
+        self.src[..pos]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+    }
+
+    /// Recognise an `@name` mention starting at `pos`.
+    fn mention(&self, pos: usize, end: usize) -> Option<Markup> {
+        let name = &self.src[pos + 1..end];
+        let len = name
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '-')))
+            .unwrap_or(name.len());
+        let len = name[..len].trim_end_matches('.').len();
+        if len == 0 {
+            return None;
+        }
+        Some(Markup {
+            inner: Inner::Literal(pos..pos + 1 + len),
+            next: pos + 1 + len,
+            style: SpanStyle::MENTION,
+        })
+    }
+
+    /// Recognise a known `:shortcode:` starting at `pos`.
+    fn shortcode(&self, pos: usize, end: usize) -> Option<Markup> {
+        let rest = &self.src[pos + 1..end];
+        let len = rest
+            .bytes()
+            .take(MAX_SHORTCODE_LEN + 1)
+            .position(|b| b == b':')?;
+        let name = &rest[..len];
+        let (_, fallback) = SHORTCODES.iter().find(|(code, _)| *code == name)?;
+        Some(Markup {
+            inner: Inner::Text(fallback),
+            next: pos + len + 2,
+            style: SpanStyle::PLAIN,
+        })
+    }
+
+    /// Recognise a bare `https://` or `todoist://` URL starting at `pos`. Web
+    /// links collapse to their host, app links to "Todoist".
+    fn bare_url(&self, pos: usize, end: usize) -> Option<Markup> {
+        let rest = &self.src[pos..end];
+        let scheme = URL_SCHEMES
+            .iter()
+            .chain([&TODOIST_SCHEME])
+            .find(|scheme| rest.starts_with(**scheme))?;
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let url = rest[..len].trim_end_matches(URL_TRAILING);
+        let next = pos + url.len();
+
+        if *scheme == TODOIST_SCHEME {
+            return (url.len() > scheme.len()).then_some(Markup {
+                inner: Inner::Text("Todoist"),
+                next,
+                style: SpanStyle::LINK,
+            });
+        }
+
+        // The host sits between any `user@` and any `:port` or path.
+        let authority = &url[scheme.len()..];
+        let authority = &authority[..authority.find(['/', '?', '#']).unwrap_or(authority.len())];
+        let mut host_start = authority.rfind('@').map_or(0, |at| at + 1);
+        let host_end = authority[host_start..]
+            .find(':')
+            .map_or(authority.len(), |colon| host_start + colon);
+        if authority[host_start..host_end].starts_with("www.") {
+            host_start += "www.".len();
+        }
+        if host_start >= host_end {
+            return None;
+        }
+        let offset = pos + scheme.len();
+        Some(Markup {
+            inner: Inner::Literal(offset + host_start..offset + host_end),
+            next,
+            style: SpanStyle::LINK,
+        })
+    }
+
+    /// Recognise `[label](url)` given the offset of the label. The label is
+    /// given `style`.
+    fn link(&mut self, label: usize, end: usize, style: SpanStyle) -> Option<Markup> {
+        let close = self.find(Closer::Bracket, label, end)?;
+        if !self.src[close + 1..end].starts_with('(') {
+            return None;
+        }
+        let url_end = self.find(Closer::Paren, close + 2, end)?;
+        Some(Markup {
+            inner: Inner::Parsed(label..close),
+            next: url_end + 1,
+            style,
+        })
+    }
+
+    /// The offset of the first `closer` at or after `from` that ends by `end`.
+    fn find(&mut self, closer: Closer, from: usize, end: usize) -> Option<usize> {
+        let at = match self.found[closer as usize] {
+            Some((searched, at)) if searched <= from && at.map_or(true, |at| at >= from) => at,
+            _ => {
+                let at = self.src[from..]
+                    .find(closer.pattern())
+                    .map(|offset| from + offset);
+                self.found[closer as usize] = Some((from, at));
+                at
+            }
+        };
+        at.filter(|&at| at + closer.pattern().len() <= end)
+    }
+}
 
 /// Strip markdown control sequences from a string, returning the plain text
 /// representation.
@@ -14,70 +519,42 @@ use alloc::string::String;
 /// - `[link](https://example.com)`
 /// - `![image](https://example.com/image.png)`
 /// - `# Header`
+/// - `!!highlight!!`, bare URLs, `@mentions`, `:emoji:` and `\*` escapes
+///
+/// Text longer than `max_len` characters is cut short, ending in `...`.
 pub fn strip(src: &str, max_len: usize) -> String {
-    if src.is_empty() {
-        return String::new();
-    }
-
-    let src = src.trim_start_matches("# ");
-
-    let mut result = String::new();
-    let mut i = 0;
-    while i < src.len() && result.len() < max_len {
-        match (
-            src.chars().nth(i).unwrap_or_default(),
-            src.chars().nth(i + 1),
-        ) {
-            (c, Some(c2)) if c == c2 && (c == '*' || c == '_' || c == '~') => {
-                i += 2;
-                let end = src[i..]
-                    .find(&format!("{}{}", c, c2))
-                    .unwrap_or(src.len() - i);
-                result.push_str(&src[i..i + end]);
-                i += end + 2;
-            }
-            (c, _) if c == '_' || c == '~' || c == '*' || c == '`' => {
-                i += 1;
-                let end = src[i..].find(c).unwrap_or(src.len() - i);
-                result.push_str(&src[i..i + end]);
-                i += end + 1;
-            }
-            ('[', _) => {
-                i += 1;
-                let end = src[i..].find(']').unwrap_or(src.len() - i);
-                result.push_str(&strip(&src[i..i + end], max_len - result.len()));
-                i += end + 1;
-
-                let end = src[i..].find(')').unwrap_or(src.len() - i);
-                i += end + 1;
-            }
-            ('!', Some('[')) => {
-                i += 2;
-                let end = src[i..].find(']').unwrap_or(src.len() - i);
-                result.push_str(&strip(&src[i..i + end], max_len - result.len()));
-                i += end + 1;
-
-                let end = src[i..].find(')').unwrap_or(src.len() - i);
-                i += end + 1;
-            }
-            (c, _) => {
-                result.push(c);
-                i += 1;
-            }
-        }
-    }
+    parse_spans(src, max_len).to_string()
+}
 
-    if result.len() > max_len {
-        result.truncate(max_len - 3);
-        result.push_str("...");
-    }
+/// Parse the same Markdown as [`strip`], keeping the emphasis of each run of
+/// text. Link and image targets are dropped; link labels are styled as links.
+pub fn parse_spans(src: &str, max_len: usize) -> RichText {
+    let src = strip_heading(src);
+    let mut text = RichText::default();
+    Parser::new(src).walk(0, src.len(), SpanStyle::PLAIN, &mut text);
+    text.truncate(max_len);
+    text
+}
 
-    result
+/// Remove a leading `#`-style heading marker.
+fn strip_heading(src: &str) -> &str {
+    let rest = src.trim_start_matches('#');
+    match rest.strip_prefix(' ') {
+        Some(heading) if rest.len() < src.len() => heading.trim_start(),
+        _ => src,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::strip;
+    use super::*;
+
+    fn span(text: &str, style: SpanStyle) -> Span {
+        Span {
+            text: text.to_string(),
+            style,
+        }
+    }
 
     #[test]
     fn passes_through_plain_text() {
@@ -100,12 +577,15 @@ mod tests {
     #[test]
     fn strips_strikethrough_and_code() {
         assert_eq!(strip("~struck~", 80), "struck");
+        assert_eq!(strip("~~struck~~", 80), "struck");
         assert_eq!(strip("`code`", 80), "code");
     }
 
     #[test]
     fn strips_leading_header_marker() {
         assert_eq!(strip("# Heading", 80), "Heading");
+        assert_eq!(strip("### Heading", 80), "Heading");
+        assert_eq!(strip("#hashtag", 80), "#hashtag");
     }
 
     #[test]
@@ -119,18 +599,15 @@ mod tests {
     }
 
     #[test]
-    fn stops_at_max_len_for_plain_text() {
-        // Plain text is appended one character at a time and simply stops once
-        // the limit is reached (no ellipsis is added in this path).
+    fn truncates_plain_text_with_ellipsis() {
         let result = strip("abcdefghij", 6);
-        assert_eq!(result, "abcdef");
-        assert_eq!(result.len(), 6);
+        assert_eq!(result, "abc...");
+        assert_eq!(strip("abcdef", 6), "abcdef");
+        assert_eq!(strip("abcdef", 2), "ab");
     }
 
     #[test]
     fn truncates_with_ellipsis_when_segment_overshoots() {
-        // A formatting segment is appended in bulk, overshooting the limit, so
-        // the result is truncated and an ellipsis appended.
         let result = strip("**abcdefghij**", 6);
         assert_eq!(result, "abc...");
         assert_eq!(result.len(), 6);
@@ -143,4 +620,220 @@ mod tests {
             "Read the docs now"
         );
     }
+
+    #[test]
+    fn keeps_multibyte_text_intact() {
+        assert_eq!(strip("Café **crème** brûlée", 80), "Café crème brûlée");
+        assert_eq!(strip("日本語の*タスク*を確認", 80), "日本語のタスクを確認");
+        assert_eq!(
+            strip("Ship it 🚀 [🎉 party](https://x.y)", 80),
+            "Ship it 🚀 🎉 party"
+        );
+    }
+
+    #[test]
+    fn counts_and_truncates_in_characters() {
+        // Ten two-byte characters fit a ten-character limit...
+        assert_eq!(strip("éééééééééé", 10), "éééééééééé");
+        // ...and an eleventh cuts on a character boundary.
+        assert_eq!(strip("ééééééééééé", 10), "ééééééé...");
+        assert_eq!(strip("🚀🚀🚀🚀🚀", 4), "🚀...");
+        assert_eq!(strip("日本語", 1), "日");
+    }
+
+    #[test]
+    fn strips_nested_markup() {
+        assert_eq!(strip("**bold _and italic_**", 80), "bold and italic");
+        assert_eq!(strip("[**bold** label](https://x.y)", 80), "bold label");
+        assert_eq!(strip("~~*gone*~~", 80), "gone");
+        // Code is literal.
+        assert_eq!(strip("`**not bold**`", 80), "**not bold**");
+    }
+
+    #[test]
+    fn keeps_unterminated_markup_literally() {
+        assert_eq!(strip("**never closed", 80), "**never closed");
+        assert_eq!(strip("2 * 3 = 6", 80), "2 * 3 = 6");
+        assert_eq!(strip("`open", 80), "`open");
+        // The target of an unclosed link is just a bare URL.
+        assert_eq!(
+            strip("[label](https://example.com", 80),
+            "[label](example.com"
+        );
+        assert_eq!(strip("[x] done", 80), "[x] done");
+        assert_eq!(strip("**bold** and *", 80), "bold and *");
+    }
+
+    #[test]
+    fn leaves_underscores_inside_words() {
+        assert_eq!(
+            strip("rename snake_case_name", 80),
+            "rename snake_case_name"
+        );
+        assert_eq!(strip("an _emphasised_ word", 80), "an emphasised word");
+    }
+
+    #[test]
+    fn handles_many_unmatched_delimiters() {
+        // Quadratic scanning would make this take minutes.
+        let mut src = String::new();
+        for _ in 0..50_000 {
+            src.push_str("[![é");
+        }
+        src.push_str("]` **end");
+        assert_eq!(strip(&src, usize::MAX), src);
+    }
+
+    #[test]
+    fn parses_styled_runs() {
+        let text = parse_spans("Read **the** _fine_ [manual](https://x.y) `now`", 80);
+        assert_eq!(
+            text.spans(),
+            [
+                span("Read ", SpanStyle::PLAIN),
+                span("the", SpanStyle::BOLD),
+                span(" ", SpanStyle::PLAIN),
+                span("fine", SpanStyle::ITALIC),
+                span(" ", SpanStyle::PLAIN),
+                span("manual", SpanStyle::LINK),
+                span(" ", SpanStyle::PLAIN),
+                span("now", SpanStyle::CODE),
+            ]
+        );
+        assert_eq!(text.to_string(), "Read the fine manual now");
+    }
+
+    #[test]
+    fn nested_markup_combines_styles() {
+        let text = parse_spans("~~old **price**~~ [**sale**](https://x.y)", 80);
+        assert_eq!(
+            text.spans(),
+            [
+                span("old ", SpanStyle::STRIKETHROUGH),
+                span("price", SpanStyle::STRIKETHROUGH.with(SpanStyle::BOLD)),
+                span(" ", SpanStyle::PLAIN),
+                span("sale", SpanStyle::LINK.with(SpanStyle::BOLD)),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_runs_and_drops_empty_ones() {
+        // The image alt text is unstyled, so it joins the text around it.
+        let text = parse_spans("a ![b](c.png) d ****", 80);
+        assert_eq!(text.spans(), [span("a b d ", SpanStyle::PLAIN)]);
+        assert!(parse_spans("", 80).is_empty());
+        assert_eq!(RichText::plain("x"), parse_spans("x", 80));
+    }
+
+    #[test]
+    fn truncates_runs_with_a_plain_ellipsis() {
+        let text = parse_spans("**bold** plain", 7);
+        assert_eq!(
+            text.spans(),
+            [span("bold", SpanStyle::BOLD), span("...", SpanStyle::PLAIN)]
+        );
+        let text = parse_spans("**ünïcödé** tail", 6);
+        assert_eq!(
+            text.spans(),
+            [span("ünï", SpanStyle::BOLD), span("...", SpanStyle::PLAIN)]
+        );
+        assert_eq!(
+            parse_spans("**bold**", 2).spans(),
+            [span("bo", SpanStyle::BOLD)]
+        );
+    }
+
+    #[test]
+    fn highlights_text() {
+        assert_eq!(
+            parse_spans("Call !!today!!", 80).spans(),
+            [
+                span("Call ", SpanStyle::PLAIN),
+                span("today", SpanStyle::HIGHLIGHT)
+            ]
+        );
+        assert_eq!(strip("!![alt](x.png)", 80), "!alt");
+        assert_eq!(strip("Hi!!", 80), "Hi!!");
+    }
+
+    #[test]
+    fn collapses_bare_urls_to_their_host() {
+        assert_eq!(
+            parse_spans("See https://www.example.com/a/b?c=d now", 80).spans(),
+            [
+                span("See ", SpanStyle::PLAIN),
+                span("example.com", SpanStyle::LINK),
+                span(" now", SpanStyle::PLAIN),
+            ]
+        );
+        assert_eq!(strip("(http://user@host.io:8080/x_y_z).", 80), "(host.io).");
+        assert_eq!(strip("https://docs.rs/*star*", 80), "docs.rs");
+        // Not a URL: no host, or part of a longer word.
+        assert_eq!(
+            strip("https:// and xhttps://a.b", 80),
+            "https:// and xhttps://a.b"
+        );
+        // Link targets are never shown, however they are written.
+        assert_eq!(strip("[docs](https://example.com)", 80), "docs");
+    }
+
+    #[test]
+    fn names_todoist_app_links() {
+        assert_eq!(
+            parse_spans("Open todoist://task?id=123.", 80).spans(),
+            [
+                span("Open ", SpanStyle::PLAIN),
+                span("Todoist", SpanStyle::LINK),
+                span(".", SpanStyle::PLAIN),
+            ]
+        );
+        assert_eq!(strip("[Inbox](todoist://inbox)", 80), "Inbox");
+        assert_eq!(strip("todoist://", 80), "todoist://");
+    }
+
+    #[test]
+    fn marks_mentions() {
+        assert_eq!(
+            parse_spans("Ask @jo.smith.", 80).spans(),
+            [
+                span("Ask ", SpanStyle::PLAIN),
+                span("@jo.smith", SpanStyle::MENTION),
+                span(".", SpanStyle::PLAIN),
+            ]
+        );
+        assert_eq!(
+            parse_spans("**@José**", 80).spans(),
+            [span("@José", SpanStyle::BOLD.with(SpanStyle::MENTION))]
+        );
+        // Email addresses and a lone `@` are left alone.
+        let email = parse_spans("mail me@example.com @ noon", 80);
+        assert_eq!(
+            email.spans(),
+            [span("mail me@example.com @ noon", SpanStyle::PLAIN)]
+        );
+    }
+
+    #[test]
+    fn replaces_known_shortcodes() {
+        assert_eq!(strip("Launch :rocket: :+1:", 80), "Launch (rocket) +1");
+        assert_eq!(strip(":tada::heart:", 80), "(party)<3");
+        // Unknown shortcodes and times stay as they are.
+        assert_eq!(strip(":unknown: at 10:30:00", 80), ":unknown: at 10:30:00");
+    }
+
+    #[test]
+    fn honours_backslash_escapes() {
+        assert_eq!(strip(r"\*not italic\*", 80), "*not italic*");
+        assert_eq!(
+            strip(r"\[x\](y) \\ \@me \:rocket:", 80),
+            r"[x](y) \ @me :rocket:"
+        );
+        // Only punctuation can be escaped.
+        assert_eq!(strip(r"C:\temp", 80), r"C:\temp");
+        assert_eq!(
+            parse_spans(r"**a\*b**", 80).spans(),
+            [span("a*b", SpanStyle::BOLD)]
+        );
+    }
 }
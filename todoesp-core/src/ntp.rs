@@ -0,0 +1,332 @@
+//! SNTP (RFC 4330) client packet encoding and reply validation.
+//!
+//! The firmware only owns the UDP transport: it sends [`request`] to a server,
+//! timestamps the reply on arrival and hands both timestamps to
+//! [`parse_reply`], which checks the reply the way RFC 4330 section 5 asks a
+//! client to and computes the clock offset and round-trip delay from the four
+//! NTP timestamps.
+//!
+//! The client's clock does not need to be anywhere near the correct time: the
+//! firmware uses microseconds since boot, and the resulting offset converts
+//! that clock to Unix time.
+
+use core::fmt;
+
+/// Length of an NTP packet without extension fields or authenticator.
+pub const PACKET_LEN: usize = 48;
+/// Standard NTP server port.
+pub const NTP_PORT: u16 = 123;
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch (1970-01-01).
+pub const NTP_TO_UNIX: i64 = 2_208_988_800;
+
+/// NTP version sent in requests.
+const VERSION: u8 = 4;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+/// Leap indicator value meaning "clock not synchronised".
+const LEAP_ALARM: u8 = 3;
+/// Strata above this are unsynchronised (16) or reserved.
+const MAX_STRATUM: u8 = 15;
+
+/// A 64-bit NTP timestamp: seconds since 1900 and a 32-bit binary fraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NtpTimestamp(u64);
+
+impl NtpTimestamp {
+    pub fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    pub fn to_bits(self) -> u64 {
+        self.0
+    }
+
+    /// Encode a time given in microseconds since the Unix epoch.
+    pub fn from_unix_micros(micros: i64) -> Self {
+        let seconds = micros.div_euclid(1_000_000) + NTP_TO_UNIX;
+        let fraction = (micros.rem_euclid(1_000_000) << 32) / 1_000_000;
+        // Truncating to 32 bits wraps seconds into the current NTP era.
+        Self((seconds as u64) << 32 | fraction as u64)
+    }
+
+    /// Decode to microseconds since the Unix epoch.
+    ///
+    /// NTP seconds wrap every 136 years; following RFC 4330 section 3, values
+    /// with the top bit set are in 1968–2036 and the rest in 2036–2104.
+    pub fn to_unix_micros(self) -> i64 {
+        let mut seconds = (self.0 >> 32) as i64;
+        if seconds & 0x8000_0000 == 0 {
+            seconds += 1 << 32;
+        }
+        let fraction = (self.0 & 0xffff_ffff) as i64;
+        // Round to the nearest microsecond so encoding then decoding is exact.
+        let micros = (fraction * 1_000_000 + (1 << 31)) >> 32;
+        (seconds - NTP_TO_UNIX) * 1_000_000 + micros
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(&bytes[..8]);
+        Self(u64::from_be_bytes(raw))
+    }
+}
+
+/// Why a reply was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NtpError {
+    /// The reply is shorter than an NTP header.
+    TooShort,
+    /// The reply is not a server packet of a known version.
+    UnexpectedMode,
+    /// The server's clock is not synchronised (leap alarm or stratum > 15).
+    Unsynchronised,
+    /// The server sent a kiss-of-death packet; the code (e.g. `RATE`, `DENY`)
+    /// says why, and the client should stop using the server.
+    KissOfDeath([u8; 4]),
+    /// The reply does not echo our transmit timestamp: it is stale, or forged.
+    OriginateMismatch,
+    /// The reply carries zero or inconsistent timestamps.
+    InvalidTimestamps,
+}
+
+impl fmt::Display for NtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort => write!(f, "reply too short"),
+            Self::UnexpectedMode => write!(f, "unexpected mode or version"),
+            Self::Unsynchronised => write!(f, "server not synchronised"),
+            Self::KissOfDeath(code) => match core::str::from_utf8(code) {
+                Ok(code) => write!(f, "kiss-of-death `{code}`"),
+                Err(_) => write!(f, "kiss-of-death"),
+            },
+            Self::OriginateMismatch => write!(f, "reply does not match the request"),
+            Self::InvalidTimestamps => write!(f, "invalid timestamps"),
+        }
+    }
+}
+
+/// A validated server reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NtpSample {
+    /// Microseconds to add to the client clock to get Unix time.
+    pub offset_micros: i64,
+    /// Round-trip network delay in microseconds, excluding server processing.
+    pub delay_micros: i64,
+    /// The server's stratum (1 = primary reference).
+    pub stratum: u8,
+}
+
+impl NtpSample {
+    /// Convert a reading of the client clock to Unix microseconds.
+    pub fn unix_micros(&self, client_micros: i64) -> i64 {
+        client_micros + self.offset_micros
+    }
+}
+
+/// Build a client request whose transmit timestamp is `transmit` (T1).
+///
+/// The server echoes this as the reply's originate timestamp, which is how
+/// [`parse_reply`] matches the reply to the request.
+pub fn request(transmit: NtpTimestamp) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[0] = VERSION << 3 | MODE_CLIENT;
+    packet[40..48].copy_from_slice(&transmit.to_bits().to_be_bytes());
+    packet
+}
+
+/// Validate a server reply and compute the clock offset.
+///
+/// `originate` is the transmit timestamp sent in the [`request`] (T1) and
+/// `received` the client clock when the reply arrived (T4).
+pub fn parse_reply(
+    reply: &[u8],
+    originate: NtpTimestamp,
+    received: NtpTimestamp,
+) -> Result<NtpSample, NtpError> {
+    if reply.len() < PACKET_LEN {
+        return Err(NtpError::TooShort);
+    }
+
+    let leap = reply[0] >> 6;
+    let version = (reply[0] >> 3) & 0x7;
+    let mode = reply[0] & 0x7;
+    let stratum = reply[1];
+    if !(1..=VERSION).contains(&version) || mode != MODE_SERVER {
+        return Err(NtpError::UnexpectedMode);
+    }
+    if stratum == 0 {
+        let mut code = [0u8; 4];
+        code.copy_from_slice(&reply[12..16]);
+        return Err(NtpError::KissOfDeath(code));
+    }
+    if leap == LEAP_ALARM || stratum > MAX_STRATUM {
+        return Err(NtpError::Unsynchronised);
+    }
+
+    let echoed = NtpTimestamp::read(&reply[24..32]);
+    let server_received = NtpTimestamp::read(&reply[32..40]);
+    let server_transmit = NtpTimestamp::read(&reply[40..48]);
+    if echoed != originate {
+        return Err(NtpError::OriginateMismatch);
+    }
+    if server_received.0 == 0 || server_transmit.0 == 0 {
+        return Err(NtpError::InvalidTimestamps);
+    }
+
+    let t1 = originate.to_unix_micros();
+    let t2 = server_received.to_unix_micros();
+    let t3 = server_transmit.to_unix_micros();
+    let t4 = received.to_unix_micros();
+    let delay_micros = (t4 - t1) - (t3 - t2);
+    if t4 < t1 || t3 < t2 || delay_micros < 0 {
+        return Err(NtpError::InvalidTimestamps);
+    }
+
+    Ok(NtpSample {
+        offset_micros: ((t2 - t1) + (t3 - t4)) / 2,
+        delay_micros,
+        stratum,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01T00:00:00Z.
+    const JAN_2024: i64 = 1_704_067_200_000_000;
+
+    fn ts(micros: i64) -> NtpTimestamp {
+        NtpTimestamp::from_unix_micros(micros)
+    }
+
+    /// A server reply to a request sent at `t1`, received by the server at
+    /// `t2` and answered at `t3`.
+    fn reply(t1: NtpTimestamp, t2: i64, t3: i64) -> [u8; PACKET_LEN] {
+        let mut packet = [0u8; PACKET_LEN];
+        packet[0] = VERSION << 3 | MODE_SERVER;
+        packet[1] = 2;
+        packet[24..32].copy_from_slice(&t1.to_bits().to_be_bytes());
+        packet[32..40].copy_from_slice(&ts(t2).to_bits().to_be_bytes());
+        packet[40..48].copy_from_slice(&ts(t3).to_bits().to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn timestamps_round_trip() {
+        for micros in [0, JAN_2024, JAN_2024 + 123_456, -1_000_000] {
+            assert_eq!(ts(micros).to_unix_micros(), micros);
+        }
+        // 2040 is past the 2036 rollover of the seconds field.
+        let after_rollover = 2_208_988_800_000_000;
+        assert!(ts(after_rollover).to_bits() >> 32 < 0x8000_0000);
+        assert_eq!(ts(after_rollover).to_unix_micros(), after_rollover);
+    }
+
+    #[test]
+    fn request_is_a_client_packet_carrying_t1() {
+        let t1 = ts(5_000_000);
+        let packet = request(t1);
+        assert_eq!(packet[0], 0x23); // LI 0, VN 4, mode 3
+        assert_eq!(&packet[40..48], &t1.to_bits().to_be_bytes());
+        assert!(packet[1..40].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn computes_offset_and_delay_from_four_timestamps() {
+        // The client clock reads 5 s since boot; the real time is 2024. The
+        // request takes 30 ms to arrive, the server spends 2 ms and the reply
+        // takes 50 ms.
+        let offset = JAN_2024 - 5_000_000;
+        let t1 = 5_000_000;
+        let t2 = t1 + offset + 30_000;
+        let t3 = t2 + 2_000;
+        let t4 = t1 + 82_000;
+
+        let sample = parse_reply(&reply(ts(t1), t2, t3), ts(t1), ts(t4)).unwrap();
+        assert_eq!(sample.delay_micros, 80_000);
+        // Asymmetric paths leave an error of half the difference (10 ms).
+        assert_eq!(sample.offset_micros, offset - 10_000);
+        assert_eq!(sample.stratum, 2);
+        assert_eq!(sample.unix_micros(t4), JAN_2024 + 72_000);
+    }
+
+    #[test]
+    fn rejects_replies_that_do_not_echo_the_request() {
+        let t1 = ts(5_000_000);
+        let forged = reply(ts(4_000_000), JAN_2024, JAN_2024);
+        assert_eq!(
+            parse_reply(&forged, t1, ts(5_100_000)),
+            Err(NtpError::OriginateMismatch)
+        );
+    }
+
+    #[test]
+    fn rejects_unsynchronised_servers() {
+        let t1 = ts(5_000_000);
+        let t4 = ts(5_100_000);
+
+        let mut alarm = reply(t1, JAN_2024, JAN_2024);
+        alarm[0] |= LEAP_ALARM << 6;
+        assert_eq!(parse_reply(&alarm, t1, t4), Err(NtpError::Unsynchronised));
+
+        let mut stratum_16 = reply(t1, JAN_2024, JAN_2024);
+        stratum_16[1] = 16;
+        assert_eq!(
+            parse_reply(&stratum_16, t1, t4),
+            Err(NtpError::Unsynchronised)
+        );
+
+        let mut zero_transmit = reply(t1, JAN_2024, JAN_2024);
+        zero_transmit[40..48].fill(0);
+        assert_eq!(
+            parse_reply(&zero_transmit, t1, t4),
+            Err(NtpError::InvalidTimestamps)
+        );
+    }
+
+    #[test]
+    fn reports_kiss_of_death_codes() {
+        let t1 = ts(5_000_000);
+        let mut kod = reply(t1, 0, 0);
+        kod[1] = 0;
+        kod[12..16].copy_from_slice(b"RATE");
+        let err = parse_reply(&kod, t1, ts(5_100_000)).unwrap_err();
+        assert_eq!(err, NtpError::KissOfDeath(*b"RATE"));
+        assert_eq!(alloc::format!("{err}"), "kiss-of-death `RATE`");
+    }
+
+    #[test]
+    fn rejects_short_and_non_server_packets() {
+        let t1 = ts(5_000_000);
+        let t4 = ts(5_100_000);
+        let packet = reply(t1, JAN_2024, JAN_2024);
+        assert_eq!(parse_reply(&packet[..47], t1, t4), Err(NtpError::TooShort));
+
+        // Our own request reflected back at us.
+        assert_eq!(
+            parse_reply(&request(t1), t1, t4),
+            Err(NtpError::UnexpectedMode)
+        );
+
+        // A broadcast (mode 5) is never the answer to a unicast request
+        // (RFC 4330 §5), even one that happens to carry our timestamp.
+        let mut broadcast = packet;
+        broadcast[0] = VERSION << 3 | 5;
+        assert_eq!(
+            parse_reply(&broadcast, t1, t4),
+            Err(NtpError::UnexpectedMode)
+        );
+    }
+
+    #[test]
+    fn rejects_negative_delay() {
+        // The server claims to have spent longer than the whole round trip.
+        let t1 = 5_000_000;
+        let packet = reply(ts(t1), JAN_2024, JAN_2024 + 200_000);
+        assert_eq!(
+            parse_reply(&packet, ts(t1), ts(t1 + 100_000)),
+            Err(NtpError::InvalidTimestamps)
+        );
+    }
+}
@@ -8,18 +8,24 @@ use alloc::vec::Vec;
 use chrono::{DateTime, FixedOffset};
 
 use crate::colour::Colour;
+use crate::markdown::RichText;
 
 /// A flattened, renderer-agnostic view of a task ready to be drawn.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TaskSnapshot {
     pub marker_color: Colour,
 
-    pub title: String,
-    pub description: Option<String>,
+    pub title: RichText,
+    pub description: Option<RichText>,
 
     pub when: String,
     pub when_color: Colour,
     pub duration: Option<String>,
+    /// The task repeats, so a recurrence glyph is drawn beside its time.
+    pub recurring: bool,
+
+    pub deadline: Option<String>,
+    pub deadline_color: Colour,
 }
 
 /// Current configuration/connectivity state used to render the first-run
@@ -45,13 +51,16 @@ pub fn get_setup_tasks(now: DateTime<FixedOffset>, state: SetupState) -> Vec<Tas
 
     vec![
         TaskSnapshot {
-            title: "Configure WiFi credentials in config.rs".to_string(),
-            description: Some(
-                "Set the WIFI_SSID and WIFI_PASSWORD values in src/config.rs.".to_string(),
-            ),
+            title: RichText::plain("Configure WiFi credentials in config.rs"),
+            description: Some(RichText::plain(
+                "Set the WIFI_SSID and WIFI_PASSWORD values in src/config.rs.",
+            )),
             when: if !config_wifi { "todo" } else { "done" }.to_string(),
             when_color: Colour::Black,
             duration: None,
+            recurring: false,
+            deadline: None,
+            deadline_color: Colour::Black,
             marker_color: if !config_wifi {
                 Colour::Green
             } else {
@@ -59,11 +68,16 @@ pub fn get_setup_tasks(now: DateTime<FixedOffset>, state: SetupState) -> Vec<Tas
             },
         },
         TaskSnapshot {
-            title: "Configure Todoist API key in config.rs".to_string(),
-            description: Some("Set the TODOIST_API_KEY value in src/config.rs.".to_string()),
+            title: RichText::plain("Configure Todoist API key in config.rs"),
+            description: Some(RichText::plain(
+                "Set the TODOIST_API_KEY value in src/config.rs.",
+            )),
             when: if config_todoist { "todo" } else { "done" }.to_string(),
             when_color: Colour::Black,
             duration: None,
+            recurring: false,
+            deadline: None,
+            deadline_color: Colour::Black,
             marker_color: if !config_todoist {
                 Colour::Green
             } else {
@@ -71,13 +85,16 @@ pub fn get_setup_tasks(now: DateTime<FixedOffset>, state: SetupState) -> Vec<Tas
             },
         },
         TaskSnapshot {
-            title: "Connect to WiFi network".to_string(),
-            description: Some(
-                "Make sure that your WiFi name and password are correct.".to_string(),
-            ),
+            title: RichText::plain("Connect to WiFi network"),
+            description: Some(RichText::plain(
+                "Make sure that your WiFi name and password are correct.",
+            )),
             when: if wifi_connected { "todo" } else { "done" }.to_string(),
             when_color: Colour::Black,
             duration: None,
+            recurring: false,
+            deadline: None,
+            deadline_color: Colour::Black,
             marker_color: if wifi_connected {
                 Colour::Green
             } else {
@@ -85,14 +102,17 @@ pub fn get_setup_tasks(now: DateTime<FixedOffset>, state: SetupState) -> Vec<Tas
             },
         },
         TaskSnapshot {
-            title: "Synchronize system time".to_string(),
-            description: Some(format!(
+            title: RichText::plain("Synchronize system time"),
+            description: Some(RichText::plain(format!(
                 "Wait for NTP to sync your system time correctly, it is currently {}.",
                 now
-            )),
+            ))),
             when: if !sync_time { "todo" } else { "done" }.to_string(),
             when_color: Colour::Black,
             duration: None,
+            recurring: false,
+            deadline: None,
+            deadline_color: Colour::Black,
             marker_color: if !sync_time {
                 Colour::Green
             } else {
@@ -100,13 +120,16 @@ pub fn get_setup_tasks(now: DateTime<FixedOffset>, state: SetupState) -> Vec<Tas
             },
         },
         TaskSnapshot {
-            title: "Synchronize Todoist tasks".to_string(),
-            description: Some(
-                "Make sure that your Todoist API key is correctly configured.".to_string(),
-            ),
+            title: RichText::plain("Synchronize Todoist tasks"),
+            description: Some(RichText::plain(
+                "Make sure that your Todoist API key is correctly configured.",
+            )),
             when: "todo".to_string(),
             when_color: Colour::Black,
             duration: None,
+            recurring: false,
+            deadline: None,
+            deadline_color: Colour::Black,
             marker_color: Colour::Red,
         },
     ]
@@ -135,6 +158,7 @@ mod tests {
     fn time_step_includes_current_time() {
         let tasks = get_setup_tasks(now(), SetupState::default());
         let time_step = &tasks[3];
-        assert!(time_step.description.as_deref().unwrap().contains("2021"));
+        let description = time_step.description.as_ref().unwrap().to_string();
+        assert!(description.contains("2021"));
     }
 }
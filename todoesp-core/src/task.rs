@@ -1,4 +1,4 @@
-//! Todoist task model, JSON parsing, ordering and due-date handling.
+//! Todoist task model, JSON decoding, ordering and due-date handling.
 //!
 //! All time-dependent behaviour takes an explicit `now: DateTime<FixedOffset>`
 //! argument so that it is deterministic and host-testable (there is no
@@ -9,17 +9,16 @@ use alloc::vec::Vec;
 use core::cmp::Ordering;
 
 use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone};
-use hifijson::num::{Lex as _, LexWrite as _};
-use hifijson::str::{Lex as _, LexAlloc as _};
-use hifijson::token::Lex;
-use hifijson::{Expect, SliceLexer};
 
+use crate::codec::{DecodeError, Reader, Writer};
 use crate::colour::Colour;
+use crate::json::{from_json_object, BoundedVec, ParseError, StreamArrayParser};
 use crate::markdown;
 use crate::snapshot::TaskSnapshot;
+use crate::time::{offset_from_seconds, PosixTz};
 
 /// Parse a Todoist "filter" API response and return the tasks sorted into
-/// display order.
+/// display order, with floating due times taken as UTC (see [`sort_tasks`]).
 ///
 /// This convenience wrapper buffers the whole response in `json`. The firmware
 /// instead drives a [`TaskStreamParser`] directly so it can parse the response
@@ -29,364 +28,113 @@ pub fn parse_tasks(json: &[u8]) -> Result<Vec<Task>, ParseError> {
     let mut parser = TaskStreamParser::new();
     let mut tasks = Vec::new();
     parser.feed(json, &mut tasks)?;
+    parser.finish()?;
     tasks.sort();
     Ok(tasks)
 }
 
-/// An error encountered while parsing a Todoist task object.
+/// Sort `tasks` into display order for a device at `offset`, which is where
+/// floating due times (those without a zone) happen.
 ///
-/// The JSON tokeniser ([`hifijson`]) and this crate's hand-written deserialiser
-/// surface every failure (malformed JSON, an unexpected value type, a missing
-/// required field, an out-of-range number, ...) as this single opaque error;
-/// the firmware only needs to know that parsing failed, not why.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct ParseError;
-
-impl From<Expect> for ParseError {
-    fn from(_: Expect) -> Self {
-        ParseError
-    }
+/// The [`Ord`] implementation of [`Task`] gives the same order for a device on
+/// UTC.
+pub fn sort_tasks(tasks: &mut [Task], offset: &FixedOffset) {
+    tasks.sort_by(|a, b| a.cmp_at(b, offset));
 }
 
-/// The object key whose array value holds the individual task objects.
-const RESULTS_KEY: &[u8] = b"\"results\"";
-
-/// Incrementally extracts task objects from a Todoist "filter" API response.
-///
-/// The response has the shape `{"results":[{..task..},{..task..}],...}`. Rather
-/// than buffer the entire (potentially large) body, callers feed the response
-/// bytes in arbitrarily sized chunks via [`feed`](Self::feed). Each complete
-/// object found inside the `results` array is parsed with
-/// [`hifijson`] (via this crate's hand-written deserialiser) and pushed to the
-/// output vector, so only a single task object is ever buffered at a time —
-/// which keeps peak memory small enough for the device's tiny heap.
+/// The next instant after `now` at which the display of `tasks` changes by
+/// itself, without any change on the server.
 ///
-/// The caller is responsible for sorting the collected tasks (see
-/// [`Task`]'s [`Ord`] implementation) once the stream is exhausted.
-#[derive(Default)]
-pub struct TaskStreamParser {
-    phase: Phase,
-    /// How many leading bytes of [`RESULTS_KEY`] have matched so far.
-    key_idx: usize,
-    /// Bytes of the object currently being accumulated (reused between objects).
-    object: Vec<u8>,
-    /// Brace-nesting depth within the current object.
-    depth: u32,
-    /// Whether the cursor is inside a JSON string literal.
-    in_string: bool,
-    /// Whether the previous byte was an unescaped backslash inside a string.
-    escaped: bool,
-}
-
-#[derive(Default)]
-enum Phase {
-    /// Scanning the leading bytes for the `"results"` key.
-    #[default]
-    SeekKey,
-    /// Found the key; skipping `:`/whitespace until the opening `[`.
-    SeekArray,
-    /// Inside the array, skipping whitespace/commas between elements.
-    BetweenElements,
-    /// Accumulating the bytes of one task object.
-    InObject,
-    /// Reached the array's closing `]`; any further bytes are ignored.
-    Done,
-}
-
-impl TaskStreamParser {
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    /// Feed a chunk of response bytes, appending any newly completed tasks to
-    /// `out`. May be called repeatedly as data arrives off the network.
-    pub fn feed(&mut self, chunk: &[u8], out: &mut Vec<Task>) -> Result<(), ParseError> {
-        for &byte in chunk {
-            self.feed_byte(byte, out)?;
-        }
-        Ok(())
-    }
-
-    fn feed_byte(&mut self, byte: u8, out: &mut Vec<Task>) -> Result<(), ParseError> {
-        match self.phase {
-            Phase::SeekKey => {
-                if byte == RESULTS_KEY[self.key_idx] {
-                    self.key_idx += 1;
-                    if self.key_idx == RESULTS_KEY.len() {
-                        self.phase = Phase::SeekArray;
-                    }
-                } else {
-                    // Restart the match, allowing the current byte to begin a
-                    // fresh one. (`"results"` has no self-overlap, and only `{`
-                    // precedes it in a real response, so this is sufficient.)
-                    self.key_idx = usize::from(byte == RESULTS_KEY[0]);
-                }
-            }
-            Phase::SeekArray => {
-                if byte == b'[' {
-                    self.phase = Phase::BetweenElements;
-                }
-            }
-            Phase::BetweenElements => match byte {
-                b'{' => {
-                    self.object.clear();
-                    self.object.push(b'{');
-                    self.depth = 1;
-                    self.in_string = false;
-                    self.escaped = false;
-                    self.phase = Phase::InObject;
-                }
-                b']' => self.phase = Phase::Done,
-                _ => {} // whitespace or ',' between elements
-            },
-            Phase::InObject => {
-                self.object.push(byte);
-                if self.in_string {
-                    if self.escaped {
-                        self.escaped = false;
-                    } else {
-                        match byte {
-                            b'\\' => self.escaped = true,
-                            b'"' => self.in_string = false,
-                            _ => {}
-                        }
-                    }
-                } else {
-                    match byte {
-                        b'"' => self.in_string = true,
-                        b'{' => self.depth += 1,
-                        b'}' => {
-                            self.depth = self.depth.saturating_sub(1);
-                            if self.depth == 0 {
-                                let task = parse_task_object(&self.object)?;
-                                out.push(task);
-                                self.phase = Phase::BetweenElements;
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            Phase::Done => {}
-        }
-        Ok(())
-    }
-}
-
-// --- A tiny JSON deserialisation framework -----------------------------------
-//
-// hifijson does the tokenising; [`FromJson`] turns those tokens directly into
-// our types without an intermediate DOM and without serde. Implementations are
-// provided below for the primitive JSON types and for `Option<T>`, and the
-// Todoist model types compose them. Only a single task object is ever
-// materialised at a time, keeping peak memory small enough for the tiny heap.
-
-/// Parse exactly one Todoist task object from its raw JSON bytes.
-fn parse_task_object(bytes: &[u8]) -> Result<Task, ParseError> {
-    let mut lexer = SliceLexer::new(bytes);
-    lexer.exactly_one(Lex::ws_peek, Task::from_json)
-}
-
-/// A type that can be parsed from a single JSON value.
-///
-/// `next` is the already-peeked first non-whitespace byte of the value (the
-/// `seq`/`exactly_one` combinators hand it over); the implementation must
-/// consume exactly that one value from `lexer`.
-pub trait FromJson: Sized {
-    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError>;
-}
-
-impl FromJson for String {
-    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
-        if next != b'"' {
-            return Err(ParseError);
-        }
-        // `str_string` interprets escape sequences (borrowing from the input
-        // when it can), so a string allocates at most once — here.
-        let value = lexer.discarded().str_string().map_err(|_| ParseError)?;
-        Ok(String::from(&*value))
-    }
-}
-
-impl FromJson for bool {
-    fn from_json(_next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
-        match lexer.null_or_bool() {
-            Some(Some(value)) => Ok(value),
-            _ => Err(ParseError),
-        }
-    }
-}
-
-/// `null` parses to `None`; any other value is delegated to `T`.
-impl<T: FromJson> FromJson for Option<T> {
-    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
-        if next == b'n' {
-            return match lexer.null_or_bool() {
-                Some(None) => Ok(None),
-                _ => Err(ParseError),
-            };
-        }
-        T::from_json(next, lexer).map(Some)
-    }
-}
-
-/// Implement [`FromJson`] for integer types by lexing the number and parsing
-/// its textual form.
-macro_rules! impl_from_json_int {
-    ($($t:ty),* $(,)?) => {
-        $(impl FromJson for $t {
-            fn from_json(_next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
-                let (number, _parts) = lexer.num_string().validated().map_err(|_| ParseError)?;
-                let text: &str = number.as_ref();
-                text.parse::<$t>().map_err(|_| ParseError)
-            }
-        })*
-    };
-}
-
-impl_from_json_int!(u8, u16, u32, u64, i8, i16, i32, i64);
-
-/// Read a JSON object, invoking `on_field` for each member. When it is called,
-/// the lexer is positioned on the (already-peeked) field value passed as
-/// `value_next`, and `on_field` must consume exactly that value. The peeked
-/// `next` must be the opening `{`.
-fn read_object<F>(next: u8, lexer: &mut SliceLexer<'_>, mut on_field: F) -> Result<(), ParseError>
-where
-    F: FnMut(&str, u8, &mut SliceLexer<'_>) -> Result<(), ParseError>,
-{
-    if next != b'{' {
-        return Err(ParseError);
-    }
-    lexer
-        .discarded()
-        .seq(b'}', Lex::ws_peek, |key_next, lexer| {
-            if key_next != b'"' {
-                return Err(ParseError);
-            }
-            let key = lexer.discarded().str_string().map_err(|_| ParseError)?;
-            let key: &str = &key;
-            lexer.expect(Lex::ws_peek, b':').ok_or(ParseError)?;
-            let value_next = lexer.ws_peek().ok_or(ParseError)?;
-            on_field(key, value_next, lexer)
+/// That is the earliest of local midnight (when the header date and every
+/// date-relative label roll over) and, for each timed task, the moment it
+/// becomes "now" and the moment its duration runs out. A task's state flips
+/// just *after* those instants, so they are reported one second late.
+pub fn next_state_change(tasks: &[Task], now: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    let midnight = now
+        .date_naive()
+        .succ_opt()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| now.offset().from_local_datetime(&midnight).single())
+        .unwrap_or(now + TimeDelta::days(1));
+
+    tasks
+        .iter()
+        .filter_map(|task| {
+            let start = task.due.as_ref()?.to_datetime(now.offset())?;
+            let end = start
+                + task
+                    .duration
+                    .as_ref()
+                    .map(TimeDelta::from)
+                    .unwrap_or_default();
+            [start, end]
+                .into_iter()
+                .map(|boundary| boundary + TimeDelta::seconds(1))
+                .find(|change| *change > now)
         })
+        .fold(midnight, DateTime::min)
 }
 
-/// Recursively discard a single JSON value of any type.
-fn skip_value(next: u8, lexer: &mut SliceLexer<'_>) -> Result<(), ParseError> {
-    match next {
-        b'"' => {
-            lexer.discarded().str_ignore().map_err(|_| ParseError)?;
-        }
-        b'-' | b'0'..=b'9' => {
-            lexer.num_ignore().validate().map_err(|_| ParseError)?;
-        }
-        b't' | b'f' | b'n' => {
-            lexer.null_or_bool().ok_or(ParseError)?;
-        }
-        b'[' => {
-            lexer.discarded().seq(b']', Lex::ws_peek, skip_value)?;
-        }
-        b'{' => {
-            read_object(next, lexer, |_key, value_next, lexer| {
-                skip_value(value_next, lexer)
-            })?;
-        }
-        _ => return Err(ParseError),
+/// Incrementally extracts task objects from a Todoist "filter" API response.
+///
+/// The response has the shape
+/// `{"results":[{..task..},{..task..}],"next_cursor":"..."}`; see
+/// [`StreamArrayParser`] for how it is consumed without buffering the whole
+/// body. The caller is responsible for sorting the collected tasks (see
+/// [`sort_tasks`]) once the stream is exhausted.
+pub type TaskStreamParser = StreamArrayParser<Task>;
+
+/// The most labels kept per task; any beyond this are dropped while parsing so
+/// a single heavily-labelled task cannot exhaust the device's heap.
+pub const MAX_LABELS: usize = 8;
+
+from_json_object! {
+    /// Collects task fields as they are parsed. Only `id` and `content` are
+    /// required; every other field falls back to a sensible default so a
+    /// single missing field never discards the whole response.
+    Task => TaskBuilder {
+        id: String,
+        priority: u8 = 1,
+        child_order: i32 = 0,
+        content: String,
+        description: String = String::new(),
+        due: Option<TaskDue> = None,
+        checked: bool = false,
+        duration: Option<TaskDuration> = None,
+        labels: BoundedVec<String, MAX_LABELS> = BoundedVec::new(),
+        project_id: Option<String> = None,
+        section_id: Option<String> = None,
+        parent_id: Option<String> = None,
+        responsible_uid: Option<String> = None,
+        note_count: u32 = 0,
+        added_at: Option<String> = None,
+        deadline: Option<TaskDeadline> = None,
     }
-    Ok(())
 }
 
-impl FromJson for Task {
-    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
-        let mut builder = TaskBuilder::default();
-        read_object(next, lexer, |key, value_next, lexer| {
-            match key {
-                "id" => builder.id = Some(FromJson::from_json(value_next, lexer)?),
-                "priority" => builder.priority = Some(FromJson::from_json(value_next, lexer)?),
-                "child_order" => {
-                    builder.child_order = Some(FromJson::from_json(value_next, lexer)?)
-                }
-                "content" => builder.content = Some(FromJson::from_json(value_next, lexer)?),
-                "description" => {
-                    builder.description = Some(FromJson::from_json(value_next, lexer)?)
-                }
-                "due" => builder.due = FromJson::from_json(value_next, lexer)?,
-                "checked" => builder.checked = Some(FromJson::from_json(value_next, lexer)?),
-                "duration" => builder.duration = FromJson::from_json(value_next, lexer)?,
-                _ => skip_value(value_next, lexer)?,
-            }
-            Ok(())
-        })?;
-        builder.build()
+from_json_object! {
+    /// Collects due-date fields as they are parsed.
+    TaskDue => TaskDueBuilder {
+        date: String,
+        timezone: Option<String> = None,
+        is_recurring: bool = false,
+        #[json(rename = "string")]
+        text: Option<String> = None,
     }
 }
 
-/// Collects task fields as they are parsed, then validates the required ones.
-#[derive(Default)]
-struct TaskBuilder {
-    id: Option<String>,
-    priority: Option<u8>,
-    child_order: Option<i32>,
-    content: Option<String>,
-    description: Option<String>,
-    due: Option<TaskDue>,
-    checked: Option<bool>,
-    duration: Option<TaskDuration>,
-}
-
-impl TaskBuilder {
-    /// Build the task, requiring only `id` and `content`; every other field
-    /// falls back to a sensible default so a single missing field never
-    /// discards the whole response.
-    fn build(self) -> Result<Task, ParseError> {
-        Ok(Task {
-            id: self.id.ok_or(ParseError)?,
-            priority: self.priority.unwrap_or(1),
-            child_order: self.child_order.unwrap_or(0),
-            content: self.content.ok_or(ParseError)?,
-            description: self.description.unwrap_or_default(),
-            due: self.due,
-            checked: self.checked.unwrap_or(false),
-            duration: self.duration,
-        })
-    }
-}
-
-impl FromJson for TaskDue {
-    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
-        let mut date: Option<String> = None;
-        let mut timezone: Option<String> = None;
-        read_object(next, lexer, |key, value_next, lexer| {
-            match key {
-                "date" => date = Some(FromJson::from_json(value_next, lexer)?),
-                "timezone" => timezone = FromJson::from_json(value_next, lexer)?,
-                _ => skip_value(value_next, lexer)?,
-            }
-            Ok(())
-        })?;
-        Ok(TaskDue {
-            date: date.ok_or(ParseError)?,
-            timezone,
-        })
+from_json_object! {
+    /// Collects deadline fields as they are parsed.
+    TaskDeadline => TaskDeadlineBuilder {
+        date: String,
     }
 }
 
-impl FromJson for TaskDuration {
-    fn from_json(next: u8, lexer: &mut SliceLexer<'_>) -> Result<Self, ParseError> {
-        let mut amount: Option<u32> = None;
-        let mut unit: Option<String> = None;
-        read_object(next, lexer, |key, value_next, lexer| {
-            match key {
-                "amount" => amount = Some(FromJson::from_json(value_next, lexer)?),
-                "unit" => unit = Some(FromJson::from_json(value_next, lexer)?),
-                _ => skip_value(value_next, lexer)?,
-            }
-            Ok(())
-        })?;
-        Ok(TaskDuration {
-            amount: amount.ok_or(ParseError)?,
-            unit: unit.ok_or(ParseError)?,
-        })
+from_json_object! {
+    /// Collects duration fields as they are parsed.
+    TaskDuration => TaskDurationBuilder {
+        amount: u32,
+        unit: String,
     }
 }
 
@@ -400,6 +148,14 @@ pub struct Task {
     due: Option<TaskDue>,
     checked: bool,
     duration: Option<TaskDuration>,
+    labels: BoundedVec<String, MAX_LABELS>,
+    project_id: Option<String>,
+    section_id: Option<String>,
+    parent_id: Option<String>,
+    responsible_uid: Option<String>,
+    note_count: u32,
+    added_at: Option<String>,
+    deadline: Option<TaskDeadline>,
 }
 
 impl Eq for Task {}
@@ -412,8 +168,23 @@ impl PartialEq for Task {
 
 impl Ord for Task {
     fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_at(other, &offset_from_seconds(0))
+    }
+}
+
+impl PartialOrd for Task {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Task {
+    /// Order tasks for display on a device at `offset`: by due date (see
+    /// [`TaskDue::cmp_at`]), with undated tasks last, then by priority and
+    /// then by their order within the project.
+    fn cmp_at(&self, other: &Self, offset: &FixedOffset) -> Ordering {
         match (self.due.as_ref(), other.due.as_ref()) {
-            (Some(a), Some(b)) => a.cmp(b),
+            (Some(a), Some(b)) => a.cmp_at(b, offset),
             (Some(_), None) => Ordering::Less,
             (None, Some(_)) => Ordering::Greater,
             (None, None) => Ordering::Equal,
@@ -421,19 +192,55 @@ impl Ord for Task {
         .then_with(|| self.priority.cmp(&other.priority).reverse())
         .then_with(|| self.child_order.cmp(&other.child_order))
     }
-}
 
-impl PartialOrd for Task {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    /// The names of the task's labels (at most [`MAX_LABELS`] of them).
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// The ID of the project the task belongs to.
+    pub fn project_id(&self) -> Option<&str> {
+        self.project_id.as_deref()
+    }
+
+    /// The ID of the project section the task is filed under, if any.
+    pub fn section_id(&self) -> Option<&str> {
+        self.section_id.as_deref()
+    }
+
+    /// The ID of the parent task, if this is a sub-task.
+    pub fn parent_id(&self) -> Option<&str> {
+        self.parent_id.as_deref()
+    }
+
+    /// The ID of the collaborator the task is assigned to, if any.
+    pub fn responsible_uid(&self) -> Option<&str> {
+        self.responsible_uid.as_deref()
+    }
+
+    /// The number of comments on the task.
+    pub fn note_count(&self) -> u32 {
+        self.note_count
+    }
+
+    /// When the task was created, if Todoist reported a valid timestamp.
+    pub fn added_at(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(self.added_at.as_deref()?).ok()
     }
-}
 
-impl Task {
     /// Convert the task into a renderer-agnostic [`TaskSnapshot`], computing the
     /// "when" label and colours relative to `now`.
+    ///
+    /// The title is kept whole, and the description up to
+    /// [`MAX_DESCRIPTION_LEN`] bytes, as when the task is cached; the renderer
+    /// fits them to its columns with [`layout::fit`](crate::layout::fit).
     pub fn into_snapshot(self, now: DateTime<FixedOffset>) -> TaskSnapshot {
         let duration: Option<TimeDelta> = self.duration.as_ref().map(|d| d.into());
+        let recurring = self.due.as_ref().is_some_and(|due| due.is_recurring);
+        let deadline = self.deadline.as_ref().map(|deadline| deadline.state(now));
+        let description = Some(self.shown_description())
+            .filter(|description| !description.is_empty())
+            .map(|description| markdown::parse_spans(description, usize::MAX));
 
         let state = self
             .due
@@ -441,15 +248,8 @@ impl Task {
             .unwrap_or(TaskDueState::Unknown);
 
         TaskSnapshot {
-            title: markdown::strip(self.content.as_str(), 80),
-            description: if self.description.is_empty() {
-                None
-            } else {
-                Some(markdown::strip(
-                    self.description.trim().lines().next().unwrap_or_default(),
-                    120,
-                ))
-            },
+            title: markdown::parse_spans(self.content.as_str(), usize::MAX),
+            description,
             when: state.format(now),
             when_color: match state {
                 TaskDueState::NowTime => Colour::Green,
@@ -457,6 +257,14 @@ impl Task {
                 _ => Colour::Black,
             },
             duration: self.duration.map(|d| d.label()),
+            recurring,
+            deadline: deadline.as_ref().map(TaskDeadlineState::format),
+            deadline_color: match deadline {
+                Some(TaskDeadlineState::Missed(_)) => Colour::Red,
+                Some(TaskDeadlineState::Today) => Colour::Orange,
+                Some(TaskDeadlineState::Approaching(_)) => Colour::Blue,
+                _ => Colour::Black,
+            },
             marker_color: if self.checked {
                 Colour::Green
             } else {
@@ -470,15 +278,153 @@ impl Task {
             },
         }
     }
+
+    /// The trimmed description, cut to at most [`MAX_DESCRIPTION_LEN`] bytes
+    /// at a character boundary. Cutting before parsing the markdown means a
+    /// cached task renders exactly like a fetched one.
+    fn shown_description(&self) -> &str {
+        let description = self.description.trim();
+        let mut end = description.len().min(MAX_DESCRIPTION_LEN);
+        while !description.is_char_boundary(end) {
+            end -= 1;
+        }
+        &description[..end]
+    }
+
+    /// Write the fields [`into_snapshot`](Self::into_snapshot),
+    /// [`next_state_change`] and the [`Ord`] implementation use, for
+    /// [`encode_tasks`](crate::codec::encode_tasks). Labels and grouping IDs
+    /// are dropped, and only the part of the description that is shown is
+    /// kept, so that one long note can't crowd other tasks out of the record.
+    pub(crate) fn encode(&self, writer: &mut Writer) -> Option<()> {
+        let due = self.due.as_ref();
+        let mut flags = 0;
+        for (set, flag) in [
+            (self.checked, FLAG_CHECKED),
+            (due.is_some(), FLAG_DUE),
+            (due.is_some_and(|due| due.is_recurring), FLAG_RECURRING),
+            (due.is_some_and(|due| due.timezone.is_some()), FLAG_TIMEZONE),
+            (self.duration.is_some(), FLAG_DURATION),
+            (self.deadline.is_some(), FLAG_DEADLINE),
+        ] {
+            if set {
+                flags |= flag;
+            }
+        }
+
+        writer.u8(flags)?;
+        writer.u8(self.priority)?;
+        writer.signed(self.child_order.into())?;
+        writer.str(&self.id)?;
+        writer.str(&self.content)?;
+        writer.str(self.shown_description())?;
+        if let Some(due) = due {
+            writer.str(&due.date)?;
+            if let Some(timezone) = &due.timezone {
+                writer.str(timezone)?;
+            }
+        }
+        if let Some(duration) = &self.duration {
+            writer.varint(duration.amount.into())?;
+            writer.str(&duration.unit)?;
+        }
+        if let Some(deadline) = &self.deadline {
+            writer.str(&deadline.date)?;
+        }
+        Some(())
+    }
+
+    /// Read a task written by [`encode`](Self::encode).
+    pub(crate) fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let flags = reader.u8()?;
+        let priority = reader.u8()?;
+        let child_order = i32::try_from(reader.signed()?).map_err(|_| DecodeError::Invalid)?;
+        let id = reader.str()?;
+        let content = reader.str()?;
+        let description = reader.str()?;
+        let due = if flags & FLAG_DUE != 0 {
+            Some(TaskDue {
+                date: reader.str()?,
+                timezone: if flags & FLAG_TIMEZONE != 0 {
+                    Some(reader.str()?)
+                } else {
+                    None
+                },
+                is_recurring: flags & FLAG_RECURRING != 0,
+                text: None,
+            })
+        } else {
+            None
+        };
+        let duration = if flags & FLAG_DURATION != 0 {
+            Some(TaskDuration {
+                amount: reader.int()?,
+                unit: reader.str()?,
+            })
+        } else {
+            None
+        };
+        let deadline = if flags & FLAG_DEADLINE != 0 {
+            Some(TaskDeadline {
+                date: reader.str()?,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            id,
+            priority,
+            child_order,
+            content,
+            description,
+            due,
+            checked: flags & FLAG_CHECKED != 0,
+            duration,
+            labels: BoundedVec::new(),
+            project_id: None,
+            section_id: None,
+            parent_id: None,
+            responsible_uid: None,
+            note_count: 0,
+            added_at: None,
+            deadline,
+        })
+    }
 }
 
+/// The most description bytes shown or cached for a task; more than the few
+/// lines the task list has room for.
+pub const MAX_DESCRIPTION_LEN: usize = 256;
+
+/// Bits of the flags byte that starts each encoded task.
+const FLAG_CHECKED: u8 = 1 << 0;
+const FLAG_DUE: u8 = 1 << 1;
+const FLAG_RECURRING: u8 = 1 << 2;
+const FLAG_TIMEZONE: u8 = 1 << 3;
+const FLAG_DURATION: u8 = 1 << 4;
+const FLAG_DEADLINE: u8 = 1 << 5;
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct TaskDue {
     date: String,
     timezone: Option<String>,
+    is_recurring: bool,
+    /// The due date as the user typed it, e.g. "every weekday at 9am".
+    text: Option<String>,
 }
 
 impl TaskDue {
+    /// Whether the task repeats (and so comes back after being completed).
+    pub fn is_recurring(&self) -> bool {
+        self.is_recurring
+    }
+
+    /// The human-readable form of the due date, such as "every weekday".
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
     /// Compute the display state of this due date relative to `now`.
     pub fn state(&self, now: DateTime<FixedOffset>, duration: Option<TimeDelta>) -> TaskDueState {
         match self.to_datetime(now.offset()) {
@@ -498,8 +444,13 @@ impl TaskDue {
 
     /// Parse the due date as a concrete instant, if it carries a time component.
     ///
-    /// Floating due dates (no timezone) are interpreted in the supplied
-    /// `offset`; fixed due dates are stored in UTC (RFC 3339) and converted.
+    /// Fixed-timezone due dates are stored in UTC or with an explicit RFC 3339
+    /// offset (e.g. `2018-11-15T12:00:00.000000Z`, `2018-11-15T14:00:00+02:00`)
+    /// and name the zone they were created in. A wall-clock time with a
+    /// `timezone` is interpreted in that zone (see [`PosixTz::from_name`]).
+    /// Floating due dates (no offset, no timezone) follow the device, so they
+    /// are interpreted in the supplied `offset`. The result is always expressed
+    /// in `offset`.
     fn to_datetime(&self, offset: &FixedOffset) -> Option<DateTime<FixedOffset>> {
         // Only due dates that carry a time component (e.g. `2018-11-15T12:00:00`)
         // can be turned into a concrete instant.
@@ -508,19 +459,37 @@ impl TaskDue {
         }
 
         let dt = self.date.as_str();
-        if dt.ends_with('Z') {
-            // Due dates with a fixed timezone are stored in UTC (RFC 3339).
-            DateTime::parse_from_rfc3339(dt)
-                .ok()
-                .map(|dt| dt.with_timezone(offset))
-        } else {
-            // Floating due dates have no timezone and may carry fractional
-            // seconds (e.g. `2018-11-15T12:00:00.000000`); interpret them in the
-            // configured local timezone.
-            let dt_no_frac = dt.split('.').next().unwrap_or(dt);
-            NaiveDateTime::parse_from_str(dt_no_frac, "%Y-%m-%dT%H:%M:%S")
-                .ok()
-                .and_then(|ndt| offset.from_local_datetime(&ndt).single())
+        if let Ok(instant) = DateTime::parse_from_rfc3339(dt) {
+            return Some(instant.with_timezone(offset));
+        }
+
+        // No offset in the date itself: a wall-clock time, possibly with
+        // fractional seconds (e.g. `2018-11-15T12:00:00.000000`). Zones that
+        // can't be resolved fall back to the device offset, the same as a
+        // floating date.
+        let local = NaiveDateTime::parse_from_str(dt, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+        self.timezone
+            .as_deref()
+            .and_then(PosixTz::from_name)
+            .and_then(|zone| zone.resolve_local(&local))
+            .or_else(|| offset.from_local_datetime(&local).single())
+            .map(|dt| dt.with_timezone(offset))
+    }
+
+    /// Order due dates chronologically for a device at `offset`.
+    ///
+    /// Timed due dates are compared as instants, so `10:00+02:00` comes before
+    /// `09:00Z`, and floating times happen in `offset`. All-day due dates sort
+    /// before timed ones on the same day. Dates that can't be parsed at all
+    /// are compared as text.
+    pub fn cmp_at(&self, other: &Self, offset: &FixedOffset) -> Ordering {
+        let key = |due: &Self| match due.to_datetime(offset) {
+            Some(dt) => Some((dt.date_naive(), Some(dt))),
+            None => due.to_date().map(|date| (date, None)),
+        };
+        match (key(self), key(other)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => self.date.cmp(&other.date),
         }
     }
 
@@ -532,12 +501,9 @@ impl TaskDue {
 }
 
 impl Ord for TaskDue {
+    /// Chronological order on a device at UTC; see [`cmp_at`](Self::cmp_at).
     fn cmp(&self, other: &Self) -> Ordering {
-        // The `date` field holds either a `YYYY-MM-DD` value or a datetime
-        // (`YYYY-MM-DDTHH:MM:SS[Z]`). Both share the same leading layout, so a
-        // lexical comparison orders them chronologically (all-day items, which
-        // lack a time component, sort before timed items on the same day).
-        self.date.cmp(&other.date)
+        self.cmp_at(other, &offset_from_seconds(0))
     }
 }
 
@@ -581,6 +547,59 @@ impl TaskDueState {
     }
 }
 
+/// How many days ahead of a deadline it counts as
+/// [`Approaching`](TaskDeadlineState::Approaching).
+pub const DEADLINE_APPROACHING_DAYS: i64 = 3;
+
+/// The date by which a task must be finished, independent of when it is
+/// scheduled (its [`TaskDue`]).
+#[derive(Debug, PartialEq, Eq)]
+pub struct TaskDeadline {
+    date: String,
+}
+
+impl TaskDeadline {
+    /// Compute the display state of this deadline relative to `now`.
+    pub fn state(&self, now: DateTime<FixedOffset>) -> TaskDeadlineState {
+        let Ok(date) = NaiveDate::parse_from_str(&self.date, "%Y-%m-%d") else {
+            return TaskDeadlineState::Unknown;
+        };
+        let today = now.date_naive();
+        match (date - today).num_days() {
+            days if days < 0 => TaskDeadlineState::Missed(date),
+            0 => TaskDeadlineState::Today,
+            days if days <= DEADLINE_APPROACHING_DAYS => TaskDeadlineState::Approaching(date),
+            _ => TaskDeadlineState::Later(date),
+        }
+    }
+}
+
+/// The state of a task's deadline, relative to "now".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskDeadlineState {
+    Unknown,
+    /// The deadline has passed.
+    Missed(NaiveDate),
+    /// The deadline is today.
+    Today,
+    /// The deadline is within [`DEADLINE_APPROACHING_DAYS`].
+    Approaching(NaiveDate),
+    /// The deadline is further away than that.
+    Later(NaiveDate),
+}
+
+impl TaskDeadlineState {
+    /// Render the short deadline label shown next to a task.
+    pub fn format(&self) -> String {
+        match self {
+            Self::Unknown => "deadline".to_string(),
+            Self::Missed(date) => date.format("missed %d/%m").to_string(),
+            Self::Today => "by today".to_string(),
+            Self::Approaching(date) | Self::Later(date) => date.format("by %d/%m").to_string(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TaskDuration {
     amount: u32,
@@ -621,7 +640,24 @@ fn naive(date: NaiveDate, h: u32, m: u32) -> NaiveDateTime {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::time::offset_from_seconds;
+    use crate::codec;
+    use crate::json::ParseErrorKind;
+
+    fn due(date: &str) -> TaskDue {
+        TaskDue {
+            date: date.to_string(),
+            timezone: None,
+            is_recurring: false,
+            text: None,
+        }
+    }
+
+    fn due_in(date: &str, timezone: &str) -> TaskDue {
+        TaskDue {
+            timezone: Some(timezone.to_string()),
+            ..due(date)
+        }
+    }
 
     fn now_at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<FixedOffset> {
         let offset = offset_from_seconds(0);
@@ -700,6 +736,73 @@ mod tests {
         assert_eq!(tasks.len(), 1);
     }
 
+    #[test]
+    fn streaming_parser_captures_next_cursor() {
+        let json = br#"{"results":[{"id":"x","priority":1,"child_order":0,"content":"hi","description":"","due":null,"checked":false,"duration":null}],"next_cursor":"abc\/123"}"#;
+        let mut parser = TaskStreamParser::new();
+        let mut tasks = Vec::new();
+        parser.feed(json, &mut tasks).expect("valid json");
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(parser.next_cursor(), Some("abc/123"));
+    }
+
+    #[test]
+    fn streaming_parser_handles_null_cursor_before_results() {
+        let json = br#"{"next_cursor": null, "results":[{"id":"x","priority":1,"child_order":0,"content":"hi","description":"","due":null,"checked":false,"duration":null}]}"#;
+        let mut parser = TaskStreamParser::new();
+        let mut tasks = Vec::new();
+        for &byte in json.iter() {
+            parser.feed(&[byte], &mut tasks).expect("valid json");
+        }
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(parser.next_cursor(), None);
+    }
+
+    #[test]
+    fn streaming_parser_follows_pages() {
+        // Two pages of a paginated response, as returned for successive
+        // requests: the first names a cursor, the last ends with `null`.
+        const PAGES: [&[u8]; 2] = [
+            br#"{"results":[{"id":"b","priority":1,"child_order":3,"content":"Second","due":{"date":"2021-01-01T09:00:00Z"}},{"id":"c","priority":1,"child_order":1,"content":"Third","due":null}],"next_cursor":"page-2"}"#,
+            br#"{"results":[{"id":"a","priority":4,"child_order":1,"content":"First","due":{"date":"2021-01-01"}}],"next_cursor":null}"#,
+        ];
+
+        let mut tasks = Vec::new();
+        let mut cursors = Vec::new();
+        for page in PAGES {
+            let mut parser = TaskStreamParser::new();
+            for chunk in page.chunks(7) {
+                parser.feed(chunk, &mut tasks).expect("valid json");
+            }
+            parser.finish().expect("complete page");
+            cursors.push(parser.into_next_cursor());
+        }
+        tasks.sort();
+
+        assert_eq!(cursors, [Some("page-2".to_string()), None]);
+        let ids: Vec<&str> = tasks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn rejects_a_truncated_page() {
+        // Cut off after the last task, before the cursor: without the check
+        // this looks like a complete final page.
+        let page = br#"{"results":[{"id":"b","content":"Second"}],"next_cursor":"page-2"}"#;
+        let cut = b"{\"results\":[{\"id\":\"b\",\"content\":\"Second\"}]".len();
+        let mut parser = TaskStreamParser::new();
+        let mut tasks = Vec::new();
+        parser.feed(&page[..cut], &mut tasks).expect("valid so far");
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(parser.next_cursor(), None);
+        let err = parser.finish().unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedJson);
+        assert_eq!(err.offset(), Some(cut));
+
+        let err = parse_tasks(&page[..cut]).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedJson);
+    }
+
     #[test]
     fn streaming_parser_handles_empty_results() {
         let mut parser = TaskStreamParser::new();
@@ -710,25 +813,177 @@ mod tests {
         assert_eq!(tasks.len(), 0);
     }
 
+    #[test]
+    fn parse_error_reports_missing_field() {
+        let json = br#"{"results":[{"id":"x","content":"ok"},{"id":"y"}]}"#;
+        let err = parse_tasks(json).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MissingField("content"));
+        assert_eq!(err.field(), None);
+        assert_eq!(err.index(), Some(1));
+        // Missing fields are only detected once the object's closing brace
+        // has been consumed.
+        assert_eq!(err.offset(), Some(json.len() - 2));
+    }
+
+    #[test]
+    fn parse_error_reports_nested_field_path() {
+        let json = br#"{"results":[{"id":"x","content":"ok","due":{"date":17}}]}"#;
+        let err = parse_tasks(json).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::TypeMismatch);
+        assert_eq!(err.field(), Some("due.date"));
+        assert_eq!(err.index(), Some(0));
+        assert_eq!(
+            err.to_string(),
+            alloc::format!(
+                "unexpected value type in `due.date` of element #0 at byte {}",
+                err.offset().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn parse_error_offset_is_absolute_across_chunks() {
+        let json =
+            br#"{"results":[{"id":"x","content":"ok"},{"id":"y","content":"ok","priority":1000}]}"#;
+        let mut parser = TaskStreamParser::new();
+        let mut tasks = Vec::new();
+        let err = json
+            .chunks(5)
+            .try_for_each(|chunk| parser.feed(chunk, &mut tasks))
+            .unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::NumberOutOfRange);
+        assert_eq!(err.field(), Some("priority"));
+        assert_eq!(err.index(), Some(1));
+        // The lexer stops just after the offending number.
+        let end_of_number = json.windows(4).position(|w| w == b"1000").unwrap() + 4;
+        assert_eq!(err.offset(), Some(end_of_number));
+    }
+
+    #[test]
+    fn parse_error_distinguishes_malformed_json() {
+        let json = br#"{"results":[{"id":"x","content" "ok"}]}"#;
+        let err = parse_tasks(json).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedJson);
+    }
+
+    #[test]
+    fn parses_grouping_fields() {
+        let json = br#"{"results":[{"id":"x","content":"hi","labels":["home","errand"],"project_id":"p1","section_id":null,"parent_id":"t0","responsible_uid":"u9","note_count":2,"added_at":"2021-01-01T09:30:00.123456Z"}]}"#;
+        let task = parse_tasks(json).expect("valid json").remove(0);
+        assert_eq!(task.labels(), ["home", "errand"]);
+        assert_eq!(task.project_id(), Some("p1"));
+        assert_eq!(task.section_id(), None);
+        assert_eq!(task.parent_id(), Some("t0"));
+        assert_eq!(task.responsible_uid(), Some("u9"));
+        assert_eq!(task.note_count(), 2);
+        assert_eq!(
+            task.added_at().map(|at| at.timestamp()),
+            Some(1_609_493_400)
+        );
+    }
+
+    #[test]
+    fn bounds_the_label_list() {
+        let labels: Vec<String> = (0..200).map(|i| alloc::format!("\"l{i}\"")).collect();
+        let json = alloc::format!(
+            r#"{{"results":[{{"id":"x","content":"hi","labels":[{}]}}]}}"#,
+            labels.join(",")
+        );
+        let task = parse_tasks(json.as_bytes()).expect("valid json").remove(0);
+        assert_eq!(task.labels().len(), MAX_LABELS);
+        assert_eq!(task.labels()[0], "l0");
+    }
+
+    #[test]
+    fn parses_recurring_due_dates() {
+        let json = br#"{"results":[
+            {"id":"r","content":"Stand-up","due":{"date":"2021-01-01T09:00:00","is_recurring":true,"string":"every weekday at 9am"}},
+            {"id":"o","content":"One-off","due":{"date":"2021-01-01","is_recurring":false,"string":"jan 1"}}
+        ]}"#;
+        let tasks = parse_tasks(json).expect("valid json");
+        let now = now_at(2021, 1, 1, 8, 0);
+
+        let one_off = &tasks[0];
+        assert!(!one_off.due.as_ref().unwrap().is_recurring());
+        let recurring = &tasks[1];
+        let due = recurring.due.as_ref().unwrap();
+        assert!(due.is_recurring());
+        assert_eq!(due.text(), Some("every weekday at 9am"));
+
+        let snapshots: Vec<TaskSnapshot> =
+            tasks.into_iter().map(|t| t.into_snapshot(now)).collect();
+        assert!(!snapshots[0].recurring);
+        assert!(snapshots[1].recurring);
+    }
+
     #[test]
     fn all_day_sorts_before_timed_same_day() {
-        let all_day = TaskDue {
-            date: "2021-01-01".to_string(),
-            timezone: None,
-        };
-        let timed = TaskDue {
-            date: "2021-01-01T09:00:00Z".to_string(),
-            timezone: None,
-        };
+        let all_day = due("2021-01-01");
+        let timed = due("2021-01-01T09:00:00Z");
         assert_eq!(all_day.cmp(&timed), Ordering::Less);
     }
 
+    #[test]
+    fn timed_dues_sort_by_instant() {
+        // 10:00+02:00 is 08:00Z, before 09:00Z, whatever the text says.
+        let utc = offset_from_seconds(0);
+        let shifted = due("2024-01-01T10:00:00+02:00");
+        let zulu = due("2024-01-01T09:00:00.000000Z");
+        assert_eq!(shifted.cmp_at(&zulu, &utc), Ordering::Less);
+        assert_eq!(shifted.cmp(&zulu), Ordering::Less);
+
+        // 09:00 in UTC-05:00 is 14:00Z: after a floating 10:00 on a device
+        // in UTC+01:00, but before one on a device in UTC-05:00.
+        let fixed = due_in("2024-01-01T09:00:00", "-05:00");
+        let floating = due("2024-01-01T10:00:00");
+        let paris = offset_from_seconds(3600);
+        let new_york = offset_from_seconds(-5 * 3600);
+        assert_eq!(fixed.cmp_at(&floating, &paris), Ordering::Greater);
+        assert_eq!(fixed.cmp_at(&floating, &new_york), Ordering::Less);
+
+        // All-day dates still come first on their day, in the device's day.
+        let all_day = due("2024-01-01");
+        assert_eq!(all_day.cmp_at(&fixed, &paris), Ordering::Less);
+        assert_eq!(due("2024-01-02").cmp_at(&fixed, &paris), Ordering::Greater);
+        // 23:30 on the 1st in UTC-05:00 is already the 2nd in Paris.
+        let late = due_in("2024-01-01T23:30:00", "-05:00");
+        assert_eq!(due("2024-01-02").cmp_at(&late, &paris), Ordering::Less);
+
+        // Unparseable dates fall back to the text.
+        assert_eq!(due("soon").cmp_at(&zulu, &utc), Ordering::Greater);
+    }
+
+    #[test]
+    fn sorts_tasks_in_the_device_offset() {
+        let json = br#"{"results":[
+            {"id":"fixed","content":"Fixed","due":{"date":"2024-01-01T09:00:00","timezone":"-05:00"}},
+            {"id":"zulu","content":"Zulu","due":{"date":"2024-01-01T09:00:00Z"}},
+            {"id":"shifted","content":"Shifted","due":{"date":"2024-01-01T10:00:00+02:00"}},
+            {"id":"floating","content":"Floating","due":{"date":"2024-01-01T10:00:00"}}
+        ]}"#;
+        let ids = |tasks: &[Task]| tasks.iter().map(|t| t.id.clone()).collect::<Vec<_>>();
+
+        let mut tasks = parse_tasks(json).expect("valid json");
+        assert_eq!(ids(&tasks), ["shifted", "zulu", "floating", "fixed"]);
+        sort_tasks(&mut tasks, &offset_from_seconds(-5 * 3600));
+        assert_eq!(ids(&tasks), ["shifted", "zulu", "fixed", "floating"]);
+        sort_tasks(&mut tasks, &offset_from_seconds(3600));
+        assert_eq!(ids(&tasks), ["shifted", "zulu", "floating", "fixed"]);
+    }
+
+    #[cfg(feature = "tzdb")]
+    #[test]
+    fn sorts_iana_zoned_dues_by_instant() {
+        // 09:00 in New York is 14:00Z; a floating 10:00 in Paris is 09:00Z.
+        let fixed = due_in("2024-01-01T09:00:00", "America/New_York");
+        let floating = due("2024-01-01T10:00:00");
+        let paris = offset_from_seconds(3600);
+        assert_eq!(fixed.cmp_at(&floating, &paris), Ordering::Greater);
+    }
+
     #[test]
     fn due_state_today_date() {
-        let due = TaskDue {
-            date: "2021-01-01".to_string(),
-            timezone: None,
-        };
+        let due = due("2021-01-01");
         let now = now_at(2021, 1, 1, 12, 0);
         assert_eq!(due.state(now, None), TaskDueState::NowDate);
         assert_eq!(due.state(now, None).format(now), "today");
@@ -738,16 +993,10 @@ mod tests {
     fn due_state_past_and_future_dates() {
         let now = now_at(2021, 6, 15, 12, 0);
 
-        let past = TaskDue {
-            date: "2021-06-14".to_string(),
-            timezone: None,
-        };
+        let past = due("2021-06-14");
         assert!(matches!(past.state(now, None), TaskDueState::PastDate(_)));
 
-        let future = TaskDue {
-            date: "2021-06-16".to_string(),
-            timezone: None,
-        };
+        let future = due("2021-06-16");
         assert!(matches!(
             future.state(now, None),
             TaskDueState::FutureDate(_)
@@ -758,10 +1007,7 @@ mod tests {
     fn due_state_times_relative_to_now() {
         let now = now_at(2021, 1, 1, 12, 0);
 
-        let earlier = TaskDue {
-            date: "2021-01-01T09:00:00Z".to_string(),
-            timezone: None,
-        };
+        let earlier = due("2021-01-01T09:00:00Z");
         assert!(matches!(
             earlier.state(now, None),
             TaskDueState::PastTime(_)
@@ -769,10 +1015,7 @@ mod tests {
         // Same day past time renders as HH:MM.
         assert_eq!(earlier.state(now, None).format(now), "09:00");
 
-        let later = TaskDue {
-            date: "2021-01-01T15:00:00Z".to_string(),
-            timezone: None,
-        };
+        let later = due("2021-01-01T15:00:00Z");
         assert!(matches!(
             later.state(now, None),
             TaskDueState::FutureTime(_)
@@ -784,16 +1027,210 @@ mod tests {
     fn due_state_now_within_duration() {
         // A task due at 11:45 with a 30 minute duration is still "now" at 12:00.
         let now = now_at(2021, 1, 1, 12, 0);
-        let due = TaskDue {
-            date: "2021-01-01T11:45:00Z".to_string(),
-            timezone: None,
-        };
+        let due = due("2021-01-01T11:45:00Z");
         assert_eq!(
             due.state(now, Some(TimeDelta::minutes(30))),
             TaskDueState::NowTime
         );
     }
 
+    #[test]
+    fn fixed_timezone_accepts_fractional_seconds_and_offsets() {
+        let offset = offset_from_seconds(3600);
+        let expected = offset
+            .from_local_datetime(&naive(NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(), 10, 0))
+            .single();
+
+        for date in [
+            "2021-01-01T09:00:00Z",
+            "2021-01-01T09:00:00.000000Z",
+            "2021-01-01T11:00:00+02:00",
+            "2021-01-01T04:00:00.000-05:00",
+        ] {
+            let dt = due_in(date, "Europe/Madrid").to_datetime(&offset);
+            assert_eq!(
+                dt.map(|dt| dt.naive_local()),
+                expected.map(|dt| dt.naive_local()),
+                "{date}"
+            );
+        }
+    }
+
+    #[test]
+    fn fixed_timezone_wall_clock_uses_the_task_zone() {
+        // Created at 09:00 in UTC+05:30, shown on a device in UTC+01:00.
+        let offset = offset_from_seconds(3600);
+        let dt = due_in("2021-01-01T09:00:00", "UTC+05:30")
+            .to_datetime(&offset)
+            .unwrap();
+        assert_eq!(dt.offset(), &offset);
+        assert_eq!(dt.format("%H:%M").to_string(), "04:30");
+    }
+
+    #[test]
+    fn floating_due_follows_the_device_offset() {
+        let offset = offset_from_seconds(-4 * 3600);
+        let dt = due("2021-01-01T09:00:00.000000")
+            .to_datetime(&offset)
+            .unwrap();
+        assert_eq!(dt.format("%H:%M").to_string(), "09:00");
+
+        // Unknown zones are treated like a floating due date.
+        let named = due_in("2021-01-01T09:00:00", "Mars/Olympus_Mons")
+            .to_datetime(&offset)
+            .unwrap();
+        assert_eq!(named, dt);
+    }
+
+    #[cfg(feature = "tzdb")]
+    #[test]
+    fn fixed_timezone_wall_clock_resolves_iana_zones() {
+        // 09:00 in Madrid (CEST in July) is 07:00 UTC.
+        let offset = offset_from_seconds(0);
+        let dt = due_in("2021-07-01T09:00:00", "Europe/Madrid")
+            .to_datetime(&offset)
+            .unwrap();
+        assert_eq!(dt.format("%H:%M").to_string(), "07:00");
+    }
+
+    fn deadline(date: &str) -> TaskDeadline {
+        TaskDeadline {
+            date: date.to_string(),
+        }
+    }
+
+    #[test]
+    fn deadline_state_today() {
+        // Any time of day counts, right up to midnight.
+        let deadline = deadline("2021-01-01");
+        assert_eq!(
+            deadline.state(now_at(2021, 1, 1, 0, 0)),
+            TaskDeadlineState::Today
+        );
+        assert_eq!(
+            deadline.state(now_at(2021, 1, 1, 23, 59)),
+            TaskDeadlineState::Today
+        );
+        assert_eq!(
+            deadline.state(now_at(2021, 1, 1, 12, 0)).format(),
+            "by today"
+        );
+    }
+
+    #[test]
+    fn deadline_state_missed_from_the_next_day() {
+        let deadline = deadline("2020-12-31");
+        let state = deadline.state(now_at(2021, 1, 1, 0, 0));
+        assert!(matches!(state, TaskDeadlineState::Missed(_)));
+        assert_eq!(state.format(), "missed 31/12");
+    }
+
+    #[test]
+    fn deadline_state_approaching_boundary() {
+        let now = now_at(2021, 1, 1, 12, 0);
+
+        let tomorrow = deadline("2021-01-02");
+        assert!(matches!(
+            tomorrow.state(now),
+            TaskDeadlineState::Approaching(_)
+        ));
+
+        let last_approaching = deadline("2021-01-04");
+        assert!(matches!(
+            last_approaching.state(now),
+            TaskDeadlineState::Approaching(_)
+        ));
+        assert_eq!(last_approaching.state(now).format(), "by 04/01");
+
+        let later = deadline("2021-01-05");
+        assert!(matches!(later.state(now), TaskDeadlineState::Later(_)));
+    }
+
+    #[test]
+    fn deadline_state_unknown_for_unparseable_dates() {
+        let deadline = deadline("soon");
+        assert_eq!(
+            deadline.state(now_at(2021, 1, 1, 12, 0)),
+            TaskDeadlineState::Unknown
+        );
+    }
+
+    #[test]
+    fn into_snapshot_colours_the_deadline() {
+        let json = br#"{"results":[
+            {"id":"m","content":"Missed","deadline":{"date":"2020-12-30","lang":"en"}},
+            {"id":"t","content":"Today","deadline":{"date":"2021-01-01","lang":"en"}},
+            {"id":"n","content":"None","deadline":null}
+        ]}"#;
+        let now = now_at(2021, 1, 1, 12, 0);
+        let snapshots: Vec<TaskSnapshot> = parse_tasks(json)
+            .expect("valid json")
+            .into_iter()
+            .map(|t| t.into_snapshot(now))
+            .collect();
+
+        let missed = snapshots
+            .iter()
+            .find(|s| s.title.to_string() == "Missed")
+            .unwrap();
+        assert_eq!(missed.deadline.as_deref(), Some("missed 30/12"));
+        assert_eq!(missed.deadline_color, Colour::Red);
+        let today = snapshots
+            .iter()
+            .find(|s| s.title.to_string() == "Today")
+            .unwrap();
+        assert_eq!(today.deadline.as_deref(), Some("by today"));
+        assert_eq!(today.deadline_color, Colour::Orange);
+        let none = snapshots
+            .iter()
+            .find(|s| s.title.to_string() == "None")
+            .unwrap();
+        assert_eq!(none.deadline, None);
+    }
+
+    #[test]
+    fn next_state_change_defaults_to_midnight() {
+        let now = now_at(2021, 1, 1, 12, 0);
+        let json = br#"{"results":[
+            {"id":"d","content":"All day","due":{"date":"2021-01-02"}},
+            {"id":"p","content":"Past","due":{"date":"2021-01-01T09:00:00Z"}},
+            {"id":"n","content":"No due date"}
+        ]}"#;
+        let tasks = parse_tasks(json).expect("valid json");
+        assert_eq!(next_state_change(&tasks, now), now_at(2021, 1, 2, 0, 0));
+        assert_eq!(next_state_change(&[], now), now_at(2021, 1, 2, 0, 0));
+    }
+
+    #[test]
+    fn next_state_change_at_task_start_and_end() {
+        let json = br#"{"results":[
+            {"id":"s","content":"Stand-up","due":{"date":"2021-01-01T09:00:00Z"},"duration":{"amount":15,"unit":"minute"}},
+            {"id":"l","content":"Lunch","due":{"date":"2021-01-01T13:00:00Z"}}
+        ]}"#;
+        let tasks = parse_tasks(json).expect("valid json");
+        let second = TimeDelta::seconds(1);
+
+        // Before the stand-up it becomes "now" just after 09:00...
+        let change = next_state_change(&tasks, now_at(2021, 1, 1, 8, 0));
+        assert_eq!(change, now_at(2021, 1, 1, 9, 0) + second);
+        assert_eq!(
+            tasks[0]
+                .due
+                .as_ref()
+                .unwrap()
+                .state(change, Some(TimeDelta::minutes(15))),
+            TaskDueState::NowTime
+        );
+        // ...and, waking exactly at 09:00, "past" just after 09:15.
+        let change = next_state_change(&tasks, now_at(2021, 1, 1, 9, 0) + second);
+        assert_eq!(change, now_at(2021, 1, 1, 9, 15) + second);
+        // Then lunch, then midnight.
+        let change = next_state_change(&tasks, now_at(2021, 1, 1, 10, 0));
+        assert_eq!(change, now_at(2021, 1, 1, 13, 0) + second);
+        let change = next_state_change(&tasks, now_at(2021, 1, 1, 14, 0));
+        assert_eq!(change, now_at(2021, 1, 2, 0, 0));
+    }
+
     #[test]
     fn duration_label_and_delta() {
         let minutes = TaskDuration {
@@ -811,6 +1248,61 @@ mod tests {
         assert_eq!(TimeDelta::from(&days), TimeDelta::days(2));
     }
 
+    #[test]
+    fn encoded_tasks_render_identically() {
+        let json = br#"{"results":[
+            {"id":"d","priority":3,"child_order":-2,"content":"Stand-up \u00e9quipe","description":"\n  *Room* 4\nbring notes","due":{"date":"2021-01-01T09:30:00","timezone":"+01:00","is_recurring":true,"string":"every weekday"},"checked":true,"duration":{"amount":15,"unit":"minute"},"deadline":{"date":"2021-01-03"},"labels":["work"]},
+            {"id":"e","content":"Blank description","description":"   "}
+        ]}"#;
+        let mut tasks = parse_tasks(SAMPLE).expect("valid json");
+        tasks.extend(parse_tasks(json).expect("valid json"));
+
+        let mut buf = [0u8; 512];
+        let len = codec::encode_tasks(&tasks, &mut buf);
+        let decoded = codec::decode_tasks(&buf[..len]).expect("valid encoding");
+        assert_eq!(decoded, tasks);
+
+        for now in [now_at(2021, 1, 1, 7, 0), now_at(2021, 1, 1, 8, 40)] {
+            assert_eq!(
+                next_state_change(&decoded, now),
+                next_state_change(&tasks, now)
+            );
+        }
+        let now = now_at(2021, 1, 1, 8, 40);
+        let original: Vec<_> = parse_tasks(SAMPLE)
+            .unwrap()
+            .into_iter()
+            .chain(parse_tasks(json).unwrap())
+            .map(|task| task.into_snapshot(now))
+            .collect();
+        let restored: Vec<_> = decoded.into_iter().map(|t| t.into_snapshot(now)).collect();
+        assert_eq!(restored, original);
+        let description = restored[3].description.as_ref().unwrap();
+        assert_eq!(description.to_string(), "Room 4\nbring notes");
+        assert!(description.spans()[0].style.italic);
+        assert_eq!(restored[4].description, None);
+    }
+
+    #[test]
+    fn long_descriptions_are_cut_alike_when_shown_and_cached() {
+        // 'é' is two bytes, so the limit falls in the middle of one, and the
+        // cut lands inside the bold run.
+        let long = format!("x**{}**", "é".repeat(MAX_DESCRIPTION_LEN));
+        let json = format!(r#"{{"results":[{{"id":"x","content":"hi","description":"{long}"}}]}}"#);
+        let tasks = parse_tasks(json.as_bytes()).expect("valid json");
+
+        let mut buf = [0u8; 1024];
+        let len = codec::encode_tasks(&tasks, &mut buf);
+        let decoded = codec::decode_tasks(&buf[..len]).expect("valid encoding");
+        assert_eq!(decoded[0].description.len(), MAX_DESCRIPTION_LEN - 1);
+        assert!(long.starts_with(decoded[0].description.as_str()));
+
+        let now = now_at(2021, 1, 1, 12, 0);
+        let original = parse_tasks(json.as_bytes()).unwrap().remove(0);
+        let restored = decoded.into_iter().next().unwrap();
+        assert_eq!(restored.into_snapshot(now), original.into_snapshot(now));
+    }
+
     #[test]
     fn into_snapshot_strips_markdown_and_sets_colours() {
         let tasks = parse_tasks(SAMPLE).expect("valid json");
@@ -822,8 +1314,11 @@ mod tests {
             .unwrap()
             .into_snapshot(now);
 
-        assert_eq!(buy.title, "Buy milk");
-        assert_eq!(buy.description.as_deref(), Some("from the store"));
+        assert_eq!(buy.title.to_string(), "Buy milk");
+        assert!(buy.title.spans()[0].style.bold);
+        let description = buy.description.unwrap();
+        assert_eq!(description.to_string(), "from the store");
+        assert!(description.spans()[1].style.italic);
         assert_eq!(buy.duration.as_deref(), Some("30m"));
         // Priority 1 (lowest) renders a white marker.
         assert_eq!(buy.marker_color, Colour::White);
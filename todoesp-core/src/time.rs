@@ -2,10 +2,19 @@
 //!
 //! `chrono::Local` relies on an operating system timezone database which is not
 //! available in a `no_std` firmware. Instead the application is configured with
-//! a fixed UTC offset (in seconds) and all "local" times are expressed as
+//! a POSIX TZ rule (see [`PosixTz`]) which yields the UTC offset in force at any
+//! instant, and all "local" times are expressed as
 //! [`chrono::DateTime<chrono::FixedOffset>`].
 
-use chrono::{DateTime, FixedOffset, TimeZone};
+use core::fmt;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+
+#[cfg(feature = "tzdb")]
+mod tzdb {
+    // `RULES` and `ZONES`, generated by `build.rs` from `tzdata/zones.tsv`.
+    include!(concat!(env!("OUT_DIR"), "/zones.rs"));
+}
 
 /// Build a [`FixedOffset`] from a signed number of seconds east of UTC.
 ///
@@ -16,12 +25,439 @@ pub fn offset_from_seconds(seconds: i32) -> FixedOffset {
         .unwrap_or_else(|| FixedOffset::east_opt(0).expect("UTC is valid"))
 }
 
+/// Parse a numeric UTC offset such as `+02:00`, `-0530`, `UTC+1` or `Z`.
+///
+/// An optional `UTC`/`GMT` prefix is accepted; a bare prefix means UTC. Zone
+/// names (e.g. `Europe/Madrid`) return `None`.
+pub fn parse_utc_offset(text: &str) -> Option<FixedOffset> {
+    let text = text.trim();
+    let rest = text
+        .strip_prefix("UTC")
+        .or_else(|| text.strip_prefix("GMT"))
+        .unwrap_or(text);
+    if rest == "Z" || (rest.is_empty() && !text.is_empty()) {
+        return FixedOffset::east_opt(0);
+    }
+
+    let (sign, rest) = match rest.as_bytes().first()? {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() > 2 => rest.split_at(rest.len() - 2),
+        None => (rest, "0"),
+    };
+    if hours.is_empty() || hours.len() > 2 || minutes.len() > 2 {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
 /// Convert a Unix timestamp (seconds since the epoch, UTC) into a local
 /// [`DateTime`] using the supplied [`FixedOffset`].
 pub fn local_from_unix(unix_seconds: i64, offset: FixedOffset) -> Option<DateTime<FixedOffset>> {
     offset.timestamp_opt(unix_seconds, 0).single()
 }
 
+/// A parsed POSIX TZ rule such as `GMT0IST,M3.5.0/1,M10.5.0`.
+///
+/// The rule names a standard offset and, optionally, a daylight saving offset
+/// with the dates it starts and ends each year. This is the format of the `TZ`
+/// environment variable and of the last line of a TZif file, so rules for any
+/// IANA zone can be copied from `/usr/share/zoneinfo`.
+///
+/// A rule displays as a TZ string that [`parse`](Self::parse) reads back to
+/// the same rule, though with placeholder zone abbreviations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PosixTz {
+    std: FixedOffset,
+    dst: Option<DstRule>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DstRule {
+    offset: FixedOffset,
+    start: Transition,
+    end: Transition,
+}
+
+/// A yearly transition: a day of the year and a time of day, in seconds after
+/// local midnight (which may be negative or beyond 24 hours).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transition {
+    day: TransitionDay,
+    time: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransitionDay {
+    /// `Jn`: day 1..=365, never counting 29 February.
+    Julian(u16),
+    /// `n`: zero-based day 0..=365, counting 29 February in leap years.
+    Ordinal(u16),
+    /// `Mm.w.d`: weekday `d` (0 = Sunday) of week `w` (5 = last) of month `m`.
+    Month { month: u8, week: u8, weekday: u8 },
+}
+
+impl PosixTz {
+    /// Coordinated Universal Time, with no daylight saving.
+    pub fn utc() -> Self {
+        Self::fixed(FixedOffset::east_opt(0).expect("UTC is valid"))
+    }
+
+    /// A zone that is always at `offset`.
+    pub fn fixed(offset: FixedOffset) -> Self {
+        Self {
+            std: offset,
+            dst: None,
+        }
+    }
+
+    /// Parse a POSIX TZ string, e.g. `CET-1CEST,M3.5.0,M10.5.0/3`.
+    ///
+    /// Zone abbreviations may be alphabetic or quoted (`<+0530>-5:30`). As in
+    /// the string itself, offsets are hours *west* of UTC. A daylight saving
+    /// zone without a rule uses the US rule (`M3.2.0,M11.1.0`), as glibc does.
+    /// Returns `None` if the string is malformed.
+    pub fn parse(rule: &str) -> Option<Self> {
+        let mut cursor = Cursor {
+            bytes: rule.as_bytes(),
+            pos: 0,
+        };
+
+        cursor.name()?;
+        let std_west = cursor.offset(24)?;
+        let std = FixedOffset::west_opt(std_west)?;
+        if cursor.done() {
+            return Some(Self::fixed(std));
+        }
+
+        cursor.name()?;
+        let dst_west = match cursor.peek() {
+            Some(b',') | None => std_west - 3600,
+            _ => cursor.offset(24)?,
+        };
+        let offset = FixedOffset::west_opt(dst_west)?;
+        let (start, end) = if cursor.done() {
+            (
+                Transition::new(TransitionDay::Month {
+                    month: 3,
+                    week: 2,
+                    weekday: 0,
+                }),
+                Transition::new(TransitionDay::Month {
+                    month: 11,
+                    week: 1,
+                    weekday: 0,
+                }),
+            )
+        } else {
+            cursor.expect(b',')?;
+            let start = cursor.transition()?;
+            cursor.expect(b',')?;
+            (start, cursor.transition()?)
+        };
+
+        cursor.done().then_some(Self {
+            std,
+            dst: Some(DstRule { offset, start, end }),
+        })
+    }
+
+    /// Look up the rule for an IANA zone name such as `Europe/Dublin`.
+    ///
+    /// The embedded table covers every zone in the IANA `zone.tab`, `UTC` and
+    /// a few common legacy names such as `Asia/Calcutta`.
+    #[cfg(feature = "tzdb")]
+    pub fn from_iana(name: &str) -> Option<Self> {
+        let index = tzdb::ZONES
+            .binary_search_by(|(zone, _)| (*zone).cmp(name))
+            .ok()?;
+        Self::parse(tzdb::RULES[usize::from(tzdb::ZONES[index].1)])
+    }
+
+    /// Resolve a zone as Todoist names it: a numeric offset such as `+02:00`,
+    /// or (with the `tzdb` feature) an IANA zone name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(offset) = parse_utc_offset(name) {
+            return Some(Self::fixed(offset));
+        }
+        #[cfg(feature = "tzdb")]
+        return Self::from_iana(name);
+        #[cfg(not(feature = "tzdb"))]
+        None
+    }
+
+    /// The UTC offset in force at the given Unix timestamp.
+    pub fn offset_at(&self, unix_seconds: i64) -> FixedOffset {
+        match &self.dst {
+            Some(dst) if dst.is_active(unix_seconds, self.std) => dst.offset,
+            _ => self.std,
+        }
+    }
+
+    /// Convert a Unix timestamp into local time under this rule.
+    pub fn local_from_unix(&self, unix_seconds: i64) -> Option<DateTime<FixedOffset>> {
+        local_from_unix(unix_seconds, self.offset_at(unix_seconds))
+    }
+
+    /// Express `instant` in local time under this rule.
+    pub fn to_local<Tz: TimeZone>(&self, instant: &DateTime<Tz>) -> DateTime<FixedOffset> {
+        instant.with_timezone(&self.offset_at(instant.timestamp()))
+    }
+
+    /// Interpret a wall-clock time under this rule.
+    ///
+    /// A time repeated when clocks go back resolves to the earlier (daylight)
+    /// instant; a time skipped when they go forward returns `None`.
+    pub fn resolve_local(&self, local: &NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        let candidates = [self.dst.map(|dst| dst.offset), Some(self.std)];
+        candidates.into_iter().flatten().find_map(|offset| {
+            let dt = offset.from_local_datetime(local).single()?;
+            (self.offset_at(dt.timestamp()) == offset).then_some(dt)
+        })
+    }
+}
+
+impl fmt::Display for PosixTz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("STD")?;
+        write_offset(f, -self.std.local_minus_utc())?;
+        if let Some(dst) = &self.dst {
+            f.write_str("DST")?;
+            write_offset(f, -dst.offset.local_minus_utc())?;
+            write!(f, ",{},{}", dst.start, dst.end)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.day {
+            TransitionDay::Julian(day) => write!(f, "J{day}")?,
+            TransitionDay::Ordinal(day) => write!(f, "{day}")?,
+            TransitionDay::Month {
+                month,
+                week,
+                weekday,
+            } => write!(f, "M{month}.{week}.{weekday}")?,
+        }
+        f.write_str("/")?;
+        write_offset(f, self.time)
+    }
+}
+
+/// Write `seconds` as a TZ string offset or time, `[-]h[:mm[:ss]]`.
+fn write_offset(f: &mut fmt::Formatter<'_>, seconds: i32) -> fmt::Result {
+    if seconds < 0 {
+        f.write_str("-")?;
+    }
+    let seconds = seconds.unsigned_abs();
+    write!(f, "{}", seconds / 3600)?;
+    match (seconds / 60 % 60, seconds % 60) {
+        (0, 0) => Ok(()),
+        (minutes, 0) => write!(f, ":{minutes:02}"),
+        (minutes, seconds) => write!(f, ":{minutes:02}:{seconds:02}"),
+    }
+}
+
+/// Express `instant` in local time in the IANA zone `name`, if it is known.
+#[cfg(feature = "tzdb")]
+pub fn local_in_zone<Tz: TimeZone>(
+    name: &str,
+    instant: &DateTime<Tz>,
+) -> Option<DateTime<FixedOffset>> {
+    PosixTz::from_iana(name).map(|tz| tz.to_local(instant))
+}
+
+impl DstRule {
+    fn is_active(&self, unix_seconds: i64, std: FixedOffset) -> bool {
+        let std_secs = i64::from(std.local_minus_utc());
+        let Some(year) = DateTime::from_timestamp(unix_seconds + std_secs, 0).map(|dt| dt.year())
+        else {
+            return false;
+        };
+
+        // The start is given in standard time and the end in daylight time.
+        let (Some(start), Some(end)) = (self.start.local_in(year), self.end.local_in(year)) else {
+            return false;
+        };
+        let start = start - std_secs;
+        let end = end - i64::from(self.offset.local_minus_utc());
+
+        if start <= end {
+            (start..end).contains(&unix_seconds)
+        } else {
+            // Southern hemisphere: daylight saving spans the new year.
+            !(end..start).contains(&unix_seconds)
+        }
+    }
+}
+
+impl Transition {
+    /// A transition at the default time of 02:00.
+    fn new(day: TransitionDay) -> Self {
+        Self {
+            day,
+            time: 2 * 3600,
+        }
+    }
+
+    /// Seconds since the epoch of this transition in `year`, as if local time
+    /// were UTC.
+    fn local_in(&self, year: i32) -> Option<i64> {
+        let date = self.day.date_in(year)?;
+        Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp() + i64::from(self.time))
+    }
+}
+
+impl TransitionDay {
+    fn date_in(&self, year: i32) -> Option<NaiveDate> {
+        match *self {
+            Self::Julian(day) => {
+                let leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+                let ordinal = if leap && day >= 60 { day + 1 } else { day };
+                NaiveDate::from_yo_opt(year, u32::from(ordinal))
+            }
+            Self::Ordinal(day) => NaiveDate::from_yo_opt(year, u32::from(day) + 1),
+            Self::Month {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = NaiveDate::from_ymd_opt(year, u32::from(month), 1)?;
+                let first_weekday = first.weekday().num_days_from_sunday();
+                let mut day = 1 + (u32::from(weekday) + 7 - first_weekday) % 7;
+                day += (u32::from(week) - 1) * 7;
+                // Week 5 means "last", which may only be the fourth.
+                while NaiveDate::from_ymd_opt(year, u32::from(month), day).is_none() {
+                    day -= 7;
+                }
+                NaiveDate::from_ymd_opt(year, u32::from(month), day)
+            }
+        }
+    }
+}
+
+/// A byte cursor over a POSIX TZ string.
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn done(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        self.eat(byte).then_some(())
+    }
+
+    /// Skip a zone abbreviation: three or more letters, or `<...>`.
+    fn name(&mut self) -> Option<()> {
+        let start = self.pos;
+        if self.eat(b'<') {
+            while self
+                .peek()
+                .is_some_and(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'-')
+            {
+                self.pos += 1;
+            }
+            let len = self.pos - start - 1;
+            self.expect(b'>')?;
+            (len >= 3).then_some(())
+        } else {
+            while self.peek().is_some_and(|b| b.is_ascii_alphabetic()) {
+                self.pos += 1;
+            }
+            (self.pos - start >= 3).then_some(())
+        }
+    }
+
+    /// Read up to `max_digits` decimal digits.
+    fn number(&mut self, max_digits: usize) -> Option<i32> {
+        let start = self.pos;
+        let mut value = 0i32;
+        while let Some(digit) = self.peek().filter(u8::is_ascii_digit) {
+            if self.pos - start == max_digits {
+                return None;
+            }
+            value = value * 10 + i32::from(digit - b'0');
+            self.pos += 1;
+        }
+        (self.pos > start).then_some(value)
+    }
+
+    /// Read `[+-]hh[:mm[:ss]]` in seconds, with hours up to `max_hours`.
+    fn offset(&mut self, max_hours: i32) -> Option<i32> {
+        let sign = if self.eat(b'-') {
+            -1
+        } else {
+            self.eat(b'+');
+            1
+        };
+        let hours = self.number(3).filter(|h| *h <= max_hours)?;
+        let mut seconds = hours * 3600;
+        if self.eat(b':') {
+            seconds += self.number(2).filter(|m| *m < 60)? * 60;
+            if self.eat(b':') {
+                seconds += self.number(2).filter(|s| *s < 60)?;
+            }
+        }
+        Some(sign * seconds)
+    }
+
+    /// Read a transition: `Jn`, `n` or `Mm.w.d`, then an optional `/time`.
+    fn transition(&mut self) -> Option<Transition> {
+        let day = if self.eat(b'J') {
+            let day = self.number(3).filter(|d| (1..=365).contains(d))?;
+            TransitionDay::Julian(day as u16)
+        } else if self.eat(b'M') {
+            let month = self.number(2).filter(|m| (1..=12).contains(m))?;
+            self.expect(b'.')?;
+            let week = self.number(1).filter(|w| (1..=5).contains(w))?;
+            self.expect(b'.')?;
+            let weekday = self.number(1).filter(|d| (0..=6).contains(d))?;
+            TransitionDay::Month {
+                month: month as u8,
+                week: week as u8,
+                weekday: weekday as u8,
+            }
+        } else {
+            let day = self.number(3).filter(|d| (0..=365).contains(d))?;
+            TransitionDay::Ordinal(day as u16)
+        };
+
+        let mut transition = Transition::new(day);
+        if self.eat(b'/') {
+            // RFC 8536 extends the time of day to ±167 hours.
+            transition.time = self.offset(167)?;
+        }
+        Some(transition)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -39,6 +475,20 @@ mod tests {
         assert_eq!(offset, FixedOffset::east_opt(0).unwrap());
     }
 
+    #[test]
+    fn parses_numeric_utc_offsets() {
+        let east = |s| FixedOffset::east_opt(s);
+        assert_eq!(parse_utc_offset("Z"), east(0));
+        assert_eq!(parse_utc_offset("UTC"), east(0));
+        assert_eq!(parse_utc_offset("+02:00"), east(7200));
+        assert_eq!(parse_utc_offset("-0530"), east(-19_800));
+        assert_eq!(parse_utc_offset("UTC+05:30"), east(19_800));
+        assert_eq!(parse_utc_offset("GMT-3"), east(-10_800));
+        assert_eq!(parse_utc_offset("Europe/Madrid"), None);
+        assert_eq!(parse_utc_offset("+24:00"), None);
+        assert_eq!(parse_utc_offset("+"), None);
+    }
+
     #[test]
     fn converts_unix_timestamp_with_offset() {
         // 2021-01-01T00:00:00Z == 2021-01-01T02:00:00+02:00
@@ -50,4 +500,172 @@ mod tests {
         assert_eq!(dt.hour(), 2);
         assert_eq!(dt.minute(), 0);
     }
+
+    /// 2024-xx-xxThh:mm:ssZ as a Unix timestamp.
+    fn utc(month: u32, day: u32, hour: u32, minute: u32) -> i64 {
+        NaiveDate::from_ymd_opt(2024, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp()
+    }
+
+    fn hours(tz: &PosixTz, unix_seconds: i64) -> f32 {
+        tz.offset_at(unix_seconds).local_minus_utc() as f32 / 3600.0
+    }
+
+    #[test]
+    fn parses_fixed_zones() {
+        assert_eq!(PosixTz::parse("UTC0"), Some(PosixTz::utc()));
+        let india = PosixTz::parse("<+0530>-5:30").unwrap();
+        assert_eq!(hours(&india, utc(6, 1, 0, 0)), 5.5);
+        let samoa = PosixTz::parse("SST11").unwrap();
+        assert_eq!(hours(&samoa, utc(6, 1, 0, 0)), -11.0);
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        for rule in [
+            "",
+            "GM0",
+            "GMT",
+            "GMT25",
+            "GMT0IST,M3.5.0",
+            "GMT0IST,M13.5.0,M10.5.0",
+            "GMT0IST,M3.6.0,M10.5.0",
+            "GMT0IST,M3.5.0/1,M10.5.0x",
+            "<+05>-5:60",
+        ] {
+            assert_eq!(PosixTz::parse(rule), None, "{rule}");
+        }
+    }
+
+    #[test]
+    fn europe_dublin_dst_boundaries() {
+        // Last Sunday of March (31st) at 01:00 GMT to the last Sunday of
+        // October (27th) at 02:00 IST, i.e. 01:00 UTC.
+        let tz = PosixTz::parse("GMT0IST,M3.5.0/1,M10.5.0").unwrap();
+        assert_eq!(hours(&tz, utc(3, 31, 0, 59)), 0.0);
+        assert_eq!(hours(&tz, utc(3, 31, 1, 0)), 1.0);
+        assert_eq!(hours(&tz, utc(10, 27, 0, 59)), 1.0);
+        assert_eq!(hours(&tz, utc(10, 27, 1, 0)), 0.0);
+
+        let local = tz.local_from_unix(utc(7, 1, 12, 0)).unwrap();
+        assert_eq!(local.format("%H:%M %:z").to_string(), "13:00 +01:00");
+    }
+
+    #[test]
+    fn us_eastern_dst_boundaries() {
+        // Second Sunday of March (10th) 02:00 EST to the first Sunday of
+        // November (3rd) 02:00 EDT.
+        let tz = PosixTz::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(hours(&tz, utc(3, 10, 6, 59)), -5.0);
+        assert_eq!(hours(&tz, utc(3, 10, 7, 0)), -4.0);
+        assert_eq!(hours(&tz, utc(11, 3, 5, 59)), -4.0);
+        assert_eq!(hours(&tz, utc(11, 3, 6, 0)), -5.0);
+
+        // Without a rule the US dates are assumed.
+        assert_eq!(PosixTz::parse("EST5EDT"), Some(tz));
+    }
+
+    #[test]
+    fn southern_hemisphere_dst_spans_the_new_year() {
+        // Sydney: first Sunday of October (6th) 02:00 AEST to the first
+        // Sunday of April (7th) 03:00 AEDT.
+        let tz = PosixTz::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(hours(&tz, utc(1, 1, 0, 0)), 11.0);
+        assert_eq!(hours(&tz, utc(4, 6, 15, 59)), 11.0);
+        assert_eq!(hours(&tz, utc(4, 6, 16, 0)), 10.0);
+        assert_eq!(hours(&tz, utc(10, 5, 15, 59)), 10.0);
+        assert_eq!(hours(&tz, utc(10, 5, 16, 0)), 11.0);
+    }
+
+    #[test]
+    fn julian_and_extended_transition_times() {
+        // J60 is 1 March even in a leap year; 59 is zero-based 29 February.
+        let julian = PosixTz::parse("XXX0YYY,J60/0,J300/0").unwrap();
+        assert_eq!(hours(&julian, utc(2, 29, 23, 59)), 0.0);
+        assert_eq!(hours(&julian, utc(3, 1, 0, 0)), 1.0);
+        let ordinal = PosixTz::parse("XXX0YYY,59/0,300/0").unwrap();
+        assert_eq!(hours(&ordinal, utc(2, 29, 0, 0)), 1.0);
+
+        // Greenland: -01 starts at 23:00 on the Saturday before the last
+        // Sunday of March, i.e. 01:00 UTC on the 31st.
+        let nuuk = PosixTz::parse("<-02>2<-01>,M3.5.0/-1,M10.5.0/0").unwrap();
+        assert_eq!(hours(&nuuk, utc(3, 31, 0, 59)), -2.0);
+        assert_eq!(hours(&nuuk, utc(3, 31, 1, 0)), -1.0);
+    }
+
+    #[test]
+    fn resolves_wall_clock_times_around_transitions() {
+        let tz = PosixTz::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        let at = |month, day, hour, minute| {
+            NaiveDate::from_ymd_opt(2024, month, day)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+
+        let summer = tz.resolve_local(&at(7, 1, 12, 0)).unwrap();
+        assert_eq!(summer.timestamp(), utc(7, 1, 16, 0));
+        // 02:30 doesn't exist on 10 March; 01:30 happens twice on 3 November.
+        assert_eq!(tz.resolve_local(&at(3, 10, 2, 30)), None);
+        let repeated = tz.resolve_local(&at(11, 3, 1, 30)).unwrap();
+        assert_eq!(repeated.timestamp(), utc(11, 3, 5, 30));
+    }
+
+    #[test]
+    fn displays_as_a_rule_that_parses_back() {
+        for rule in [
+            "UTC0",
+            "<+0530>-5:30",
+            "<-0330>3:30",
+            "GMT0IST,M3.5.0/1,M10.5.0",
+            "EST5EDT",
+            "AEST-10AEDT,M10.1.0,M4.1.0/3",
+            "XXX0YYY,J60/0,300/1:02:03",
+            "<-02>2<-01>,M3.5.0/-1,M10.5.0/0",
+        ] {
+            let tz = PosixTz::parse(rule).unwrap();
+            let shown = tz.to_string();
+            assert_eq!(PosixTz::parse(&shown), Some(tz), "{rule} as {shown}");
+        }
+        assert_eq!(
+            PosixTz::parse("<+0530>-5:30").unwrap().to_string(),
+            "STD-5:30"
+        );
+        assert_eq!(
+            PosixTz::parse("EST5EDT").unwrap().to_string(),
+            "STD5DST4,M3.2.0/2,M11.1.0/2"
+        );
+    }
+
+    #[test]
+    fn named_zones_accept_numeric_offsets() {
+        let tz = PosixTz::from_name("UTC+05:30").unwrap();
+        assert_eq!(hours(&tz, utc(1, 1, 0, 0)), 5.5);
+    }
+
+    #[cfg(feature = "tzdb")]
+    #[test]
+    fn embedded_rules_all_parse() {
+        for (name, rule) in tzdb::ZONES {
+            let tz = PosixTz::parse(tzdb::RULES[usize::from(rule)]);
+            assert!(tz.is_some(), "{name}");
+            assert_eq!(tz.and_then(|tz| PosixTz::parse(&tz.to_string())), tz);
+        }
+    }
+
+    #[cfg(feature = "tzdb")]
+    #[test]
+    fn resolves_iana_zones() {
+        let summer = DateTime::from_timestamp(utc(7, 1, 12, 0), 0).unwrap();
+        let dublin = local_in_zone("Europe/Dublin", &summer).unwrap();
+        assert_eq!(dublin.format("%H:%M %:z").to_string(), "13:00 +01:00");
+        let sydney = local_in_zone("Australia/Sydney", &summer).unwrap();
+        assert_eq!(sydney.format("%H:%M %:z").to_string(), "22:00 +10:00");
+        assert_eq!(PosixTz::from_name("UTC"), Some(PosixTz::utc()));
+        assert_eq!(PosixTz::from_iana("Mars/Olympus_Mons"), None);
+    }
 }
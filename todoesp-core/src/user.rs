@@ -0,0 +1,140 @@
+//! Todoist user profile, used to pick up the account's timezone.
+//!
+//! The device only needs a handful of fields from the (large) user object, so
+//! everything else is skipped while parsing.
+
+use alloc::string::String;
+
+use chrono::FixedOffset;
+
+use crate::json::{from_json_object, parse_json, ParseError};
+use crate::time::{parse_utc_offset, PosixTz};
+
+/// Parse a Todoist `/user` API response.
+pub fn parse_user_profile(json: &[u8]) -> Result<UserProfile, ParseError> {
+    parse_json(json)
+}
+
+from_json_object! {
+    /// Collects user fields as they are parsed. Only `id` is required.
+    UserProfile => UserProfileBuilder {
+        id: String,
+        full_name: String = String::new(),
+        tz_info: Option<UserTimezone> = None,
+    }
+}
+
+from_json_object! {
+    /// Collects timezone fields as they are parsed.
+    UserTimezone => UserTimezoneBuilder {
+        timezone: String,
+        gmt_string: Option<String> = None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UserProfile {
+    id: String,
+    full_name: String,
+    tz_info: Option<UserTimezone>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct UserTimezone {
+    /// The IANA zone name, e.g. `Europe/Dublin`.
+    timezone: String,
+    /// The zone's UTC offset when the profile was fetched, e.g. `+01:00`.
+    gmt_string: Option<String>,
+}
+
+impl UserProfile {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    /// The account's IANA timezone name, e.g. `Europe/Dublin`.
+    pub fn timezone(&self) -> Option<&str> {
+        self.tz_info.as_ref().map(|tz| tz.timezone.as_str())
+    }
+
+    /// The account's UTC offset at the time the profile was fetched.
+    pub fn utc_offset(&self) -> Option<FixedOffset> {
+        self.tz_info
+            .as_ref()
+            .and_then(|tz| tz.gmt_string.as_deref())
+            .and_then(parse_utc_offset)
+    }
+
+    /// The rule for the account's timezone.
+    ///
+    /// The zone name is resolved if possible (see [`PosixTz::from_name`]);
+    /// otherwise the offset reported with the profile is used as-is, which is
+    /// only correct until the zone's next daylight saving transition.
+    pub fn tz(&self) -> Option<PosixTz> {
+        self.timezone()
+            .and_then(PosixTz::from_name)
+            .or_else(|| self.utc_offset().map(PosixTz::fixed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::ParseErrorKind;
+
+    const SAMPLE: &[u8] = br#"{
+        "id": "2671355",
+        "email": "me@example.com",
+        "full_name": "Ada Lovelace",
+        "lang": "en",
+        "start_day": 1,
+        "features": {"beta": 1, "dateist_inline_disabled": false},
+        "tz_info": {"timezone": "America/Sao_Paulo", "gmt_string": "-03:00", "hours": -3, "minutes": 0, "is_dst": 0}
+    }"#;
+
+    #[test]
+    fn parses_profile_and_timezone() {
+        let profile = parse_user_profile(SAMPLE).expect("valid json");
+        assert_eq!(profile.id(), "2671355");
+        assert_eq!(profile.full_name(), "Ada Lovelace");
+        assert_eq!(profile.timezone(), Some("America/Sao_Paulo"));
+        assert_eq!(profile.utc_offset(), FixedOffset::west_opt(3 * 3600));
+    }
+
+    #[cfg(not(feature = "tzdb"))]
+    #[test]
+    fn falls_back_to_the_reported_offset() {
+        let profile = parse_user_profile(SAMPLE).expect("valid json");
+        assert_eq!(
+            profile.tz(),
+            FixedOffset::west_opt(3 * 3600).map(PosixTz::fixed)
+        );
+    }
+
+    #[cfg(feature = "tzdb")]
+    #[test]
+    fn resolves_the_zone_name() {
+        let profile = parse_user_profile(SAMPLE).expect("valid json");
+        assert_eq!(profile.tz(), PosixTz::from_iana("America/Sao_Paulo"));
+    }
+
+    #[test]
+    fn timezone_is_optional() {
+        let profile = parse_user_profile(br#"{"id":"1","tz_info":null}"#).expect("valid json");
+        assert_eq!(profile.timezone(), None);
+        assert_eq!(profile.tz(), None);
+    }
+
+    #[test]
+    fn reports_missing_fields_and_trailing_garbage() {
+        let err = parse_user_profile(br#"{"tz_info":{"timezone":"UTC"}}"#).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MissingField("id"));
+
+        let err = parse_user_profile(br#"{"id":"1"} x"#).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedJson);
+    }
+}